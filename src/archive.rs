@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use flate2::read::GzDecoder;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// Separates the archive from the member in virtual paths like
+/// `photos.zip!/2019/IMG_001.jpg`.
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Kind by file name: `.zip`, `.tar`, `.tar.gz` or `.tgz`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let name = path.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// A regular file inside an archive.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// Path within the archive, without a leading `./`
+    pub name: String,
+    pub size: u64,
+    pub mtime: Option<i64>,
+}
+
+/// Whether `path` is an archive [`for_each_member`] can read.
+pub fn is_archive(path: &str) -> bool {
+    ArchiveKind::from_path(path).is_some()
+}
+
+/// Whether `path` is a virtual path of an archive member. Such files can't
+/// be opened, deleted or replaced on their own.
+pub fn is_member(path: &str) -> bool {
+    split_member(path).is_some()
+}
+
+/// `archive!/member`
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{}{}{}", archive, MEMBER_SEPARATOR, member)
+}
+
+/// The archive holding the member at the virtual `path`, `None` for regular
+/// files.
+pub fn archive_path(path: &str) -> Option<&str> {
+    split_member(path).map(|(archive, _)| archive)
+}
+
+/// `(archive, member)` of a virtual path. Only a separator right after a
+/// name [`ArchiveKind`] recognises counts, so folders like `Urlaub!/` stay
+/// regular paths.
+fn split_member(path: &str) -> Option<(&str, &str)> {
+    path.match_indices(MEMBER_SEPARATOR)
+        .map(|(idx, _)| (&path[..idx], &path[idx + MEMBER_SEPARATOR.len()..]))
+        .find(|(archive, _)| is_archive(archive))
+}
+
+/// Call `f` with every regular file in the archive at `path` and a reader
+/// for its uncompressed content. Nested archives are not opened.
+pub fn for_each_member(path: &Path, mut f: impl FnMut(Member, &mut dyn Read) -> Result<()>) -> Result<()> {
+    let kind = ArchiveKind::from_path(&path.to_string_lossy())
+        .with_context(|| format!("{} is not a zip or tar archive", path.display()))?;
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(file))
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            for i in 0..zip.len() {
+                let mut entry = zip
+                    .by_index(i)
+                    .with_context(|| format!("Failed to read entry {} of {}", i, path.display()))?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let member = Member {
+                    name: entry.name().trim_start_matches("./").to_string(),
+                    size: entry.size(),
+                    mtime: entry
+                        .last_modified()
+                        .and_then(|t| NaiveDateTime::try_from(t).ok())
+                        .map(|t| t.and_utc().timestamp()),
+                };
+                f(member, &mut entry)?;
+            }
+            Ok(())
+        }
+        ArchiveKind::Tar => for_each_tar_member(path, BufReader::new(file), f),
+        ArchiveKind::TarGz => for_each_tar_member(path, GzDecoder::new(BufReader::new(file)), f),
+    }
+}
+
+fn for_each_tar_member(
+    path: &Path,
+    reader: impl Read,
+    mut f: impl FnMut(Member, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    let entries = tar
+        .entries()
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    for entry in entries {
+        let mut entry = entry.with_context(|| format!("Failed to read {}", path.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+        let member = Member {
+            name,
+            size: entry.size(),
+            mtime: entry.header().mtime().ok().map(|t| t as i64),
+        };
+        f(member, &mut entry)?;
+    }
+    Ok(())
+}
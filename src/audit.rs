@@ -0,0 +1,244 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt, fs, io,
+    time::UNIX_EPOCH,
+};
+
+use crate::archive;
+use crate::types::FileEntry;
+
+/// A problem found in a duplicates file. `group` numbers start at 1.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// The same path appears in more than one group.
+    DuplicatePath { path: String, groups: Vec<usize> },
+    /// A group with fewer than two members.
+    SingletonGroup { group: usize, members: usize },
+    /// Members of one group with different checksums.
+    MixedChecksums { group: usize, checksums: Vec<String> },
+    /// Members of one group with different sizes.
+    MixedSizes { group: usize, checksum: String, sizes: Vec<u64> },
+    /// A recorded file that no longer exists.
+    Missing { group: usize, path: String },
+    /// A file whose size on disk differs from the record.
+    SizeChanged { group: usize, path: String, recorded: u64, actual: u64 },
+    /// A file whose mtime on disk differs from the record.
+    MtimeChanged { group: usize, path: String, recorded: i64, actual: i64 },
+    /// A file that exists but could not be inspected.
+    Unreadable { group: usize, path: String, error: String },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::DuplicatePath { path, groups } => {
+                let groups: Vec<String> = groups.iter().map(|g| g.to_string()).collect();
+                write!(f, "{} is listed in groups {}", path, groups.join(", "))
+            }
+            Finding::SingletonGroup { group, members } => {
+                write!(f, "group {} has only {} member(s)", group, members)
+            }
+            Finding::MixedChecksums { group, checksums } => {
+                write!(f, "group {} mixes checksums {}", group, checksums.join(", "))
+            }
+            Finding::MixedSizes { group, checksum, sizes } => {
+                let sizes: Vec<String> = sizes.iter().map(|s| s.to_string()).collect();
+                write!(f, "group {} has checksum {} with sizes {}", group, checksum, sizes.join(", "))
+            }
+            Finding::Missing { group, path } => write!(f, "group {}: {} no longer exists", group, path),
+            Finding::SizeChanged { group, path, recorded, actual } => {
+                write!(f, "group {}: {} is {} bytes, recorded {}", group, path, actual, recorded)
+            }
+            Finding::MtimeChanged { group, path, recorded, actual } => {
+                write!(
+                    f,
+                    "group {}: {} was modified ({} -> {})",
+                    group,
+                    path,
+                    format_time(*recorded),
+                    format_time(*actual)
+                )
+            }
+            Finding::Unreadable { group, path, error } => {
+                write!(f, "group {}: {} can't be checked: {}", group, path, error)
+            }
+        }
+    }
+}
+
+fn format_time(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+/// Checks groups one at a time; call [`Auditor::finish`] for the findings.
+#[derive(Debug, Default)]
+pub struct Auditor {
+    check_disk: bool,
+    /// Only entries without a source label or with this one are on this disk.
+    local_source: Option<String>,
+    groups: usize,
+    files_checked: u64,
+    seen: HashMap<String, Vec<usize>>,
+    findings: Vec<Finding>,
+}
+
+/// Result of an audit.
+#[derive(Serialize, Debug)]
+pub struct AuditReport {
+    pub groups: usize,
+    /// Files compared against the disk.
+    pub files_checked: u64,
+    pub findings: Vec<Finding>,
+}
+
+impl Auditor {
+    /// With `check_disk`, local files are compared against the file system.
+    /// Entries of merged inventories are only checked if their source label
+    /// is `local_source`.
+    pub fn new(check_disk: bool, local_source: Option<String>) -> Self {
+        Auditor {
+            check_disk,
+            local_source,
+            ..Auditor::default()
+        }
+    }
+
+    pub fn add_group(&mut self, group: &[FileEntry]) {
+        self.groups += 1;
+        let index = self.groups;
+
+        if group.len() < 2 {
+            self.findings.push(Finding::SingletonGroup {
+                group: index,
+                members: group.len(),
+            });
+        }
+
+        let checksums: BTreeSet<&str> = group.iter().map(|e| e.checksum.as_str()).collect();
+        if checksums.len() > 1 {
+            self.findings.push(Finding::MixedChecksums {
+                group: index,
+                checksums: checksums.iter().map(|c| c.to_string()).collect(),
+            });
+        }
+
+        // sizes only matter within one checksum, the case above covers the rest
+        for checksum in &checksums {
+            let sizes: BTreeSet<u64> = group
+                .iter()
+                .filter(|e| e.checksum == *checksum)
+                .map(|e| e.size)
+                .collect();
+            if sizes.len() > 1 {
+                self.findings.push(Finding::MixedSizes {
+                    group: index,
+                    checksum: checksum.to_string(),
+                    sizes: sizes.into_iter().collect(),
+                });
+            }
+        }
+
+        for entry in group {
+            self.seen.entry(entry.path.clone()).or_default().push(index);
+            if self.check_disk && self.is_local(entry) {
+                self.check_file(index, entry);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> AuditReport {
+        let mut duplicates: Vec<Finding> = self
+            .seen
+            .into_iter()
+            .filter(|(_, groups)| groups.len() > 1)
+            .map(|(path, groups)| Finding::DuplicatePath { path, groups })
+            .collect();
+        duplicates.sort_by(|a, b| match (a, b) {
+            (Finding::DuplicatePath { path: a, .. }, Finding::DuplicatePath { path: b, .. }) => a.cmp(b),
+            _ => std::cmp::Ordering::Equal,
+        });
+        self.findings.splice(0..0, duplicates);
+
+        AuditReport {
+            groups: self.groups,
+            files_checked: self.files_checked,
+            findings: self.findings,
+        }
+    }
+
+    fn is_local(&self, entry: &FileEntry) -> bool {
+        is_local(entry, self.local_source.as_deref())
+    }
+
+    fn check_file(&mut self, group: usize, entry: &FileEntry) {
+        self.files_checked += 1;
+        let path = entry.path.clone();
+
+        // archive members can't be looked at alone, only their archive
+        let on_disk = archive::archive_path(&entry.path).unwrap_or(&entry.path);
+        let metadata = match fs::metadata(on_disk) {
+            Ok(m) => m,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.findings.push(Finding::Missing { group, path });
+                return;
+            }
+            Err(e) => {
+                self.findings.push(Finding::Unreadable {
+                    group,
+                    path,
+                    error: e.to_string(),
+                });
+                return;
+            }
+        };
+
+        if on_disk != entry.path {
+            return;
+        }
+
+        if metadata.len() != entry.size {
+            self.findings.push(Finding::SizeChanged {
+                group,
+                path: path.clone(),
+                recorded: entry.size,
+                actual: metadata.len(),
+            });
+        }
+
+        let actual_mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        if let (Some(recorded), Some(actual)) = (entry.mtime, actual_mtime) {
+            if recorded != actual {
+                self.findings.push(Finding::MtimeChanged {
+                    group,
+                    path,
+                    recorded,
+                    actual,
+                });
+            }
+        }
+    }
+}
+
+/// Whether `entry` comes from another machine: merged inventories label every
+/// entry with its source, unlabelled entries are from this one.
+pub fn is_foreign(entry: &FileEntry, local_source: Option<&str>) -> bool {
+    match (entry.source.as_deref(), local_source) {
+        (Some(source), Some(local)) => source != local,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Whether `entry` is a file on this disk: no URL and not [`is_foreign`].
+pub fn is_local(entry: &FileEntry, local_source: Option<&str>) -> bool {
+    let remote = entry.path.starts_with("http://") || entry.path.starts_with("https://");
+    !remote && !is_foreign(entry, local_source)
+}
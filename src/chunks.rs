@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use fastcdc::v2020::StreamCDC;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::Path,
+};
+use tokio::task::JoinSet;
+
+use crate::archive;
+use crate::scan::ScanError;
+use crate::types::FileEntry;
+
+/// Files smaller than this are not chunked by default.
+pub const DEFAULT_MIN_FILE_SIZE: u64 = 64 * 1_048_576;
+
+/// Average chunk size by default. Chunks are at least a quarter and at most
+/// four times this long.
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Share of the smaller file from which a pair is reported by default.
+pub const DEFAULT_MIN_RATIO: f64 = 0.5;
+
+/// Chunks found in more files than this, like runs of zeros in disk images,
+/// still count towards the savings but don't make files a pair.
+const MAX_PAIR_FANOUT: usize = 64;
+
+/// The chunks of one file, standing for all its byte-identical copies.
+#[derive(Debug, Clone)]
+pub struct FileChunks {
+    pub path: String,
+    pub size: u64,
+    /// Number of files with this content, read only once
+    pub copies: u64,
+    /// `(first 128 bits of the BLAKE3 hash, length)` in file order
+    pub chunks: Vec<(u128, u32)>,
+}
+
+/// Two files that are not identical but share chunks.
+#[derive(Serialize, Debug, Clone)]
+pub struct SharedChunks {
+    pub a: String,
+    pub a_size: u64,
+    pub b: String,
+    pub b_size: u64,
+    /// Bytes of the distinct chunks found in both files
+    pub shared_bytes: u64,
+    /// `shared_bytes` as a share of the smaller file, from 0 to 1
+    pub ratio: f64,
+}
+
+/// Result of [`analyze`].
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ChunkReport {
+    /// Files looked at, counting every copy
+    pub files: u64,
+    pub bytes: u64,
+    /// Distinct chunks and their bytes, i.e. the size of a chunk-level store
+    pub chunks: u64,
+    pub unique_bytes: u64,
+    /// Bytes saved by storing every distinct chunk once
+    pub savings: u64,
+    /// Part of `savings` that deleting byte-identical copies saves as well
+    pub file_savings: u64,
+    /// Most shared bytes first
+    pub pairs: Vec<SharedChunks>,
+}
+
+/// Split the file at `path` into content-defined chunks of about `avg_size`
+/// bytes with FastCDC and hash them.
+pub fn chunk_file(path: &Path, avg_size: u32) -> Result<Vec<(u128, u32)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    StreamCDC::new(file, avg_size / 4, avg_size, avg_size * 4)
+        .map(|chunk| {
+            let chunk = chunk.with_context(|| format!("Failed to read {}", path.display()))?;
+            let hash = blake3::hash(&chunk.data);
+            let mut prefix = [0u8; 16];
+            prefix.copy_from_slice(&hash.as_bytes()[..16]);
+            Ok((u128::from_le_bytes(prefix), chunk.length as u32))
+        })
+        .collect()
+}
+
+/// Chunk the local files among `files` of at least `min_size` bytes, one
+/// file per core at a time. Byte-identical files are read once. Files that
+/// can't be read are returned as errors.
+pub async fn chunk_files(
+    files: &[FileEntry],
+    min_size: u64,
+    avg_size: u32,
+) -> Result<(Vec<FileChunks>, Vec<ScanError>)> {
+    let mut contents: BTreeMap<(u64, &str), (&str, u64)> = BTreeMap::new();
+    for file in files
+        .iter()
+        .filter(|f| f.size >= min_size)
+        .filter(|f| !f.path.starts_with("http://") && !f.path.starts_with("https://"))
+        .filter(|f| !archive::is_member(&f.path))
+    {
+        let content = contents.entry((file.size, &file.checksum)).or_insert((&file.path, 0));
+        content.0 = content.0.min(file.path.as_str());
+        content.1 += 1;
+    }
+
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let mut chunked = Vec::new();
+    let mut errors = Vec::new();
+    let mut tasks = JoinSet::new();
+    let mut pending = contents.into_iter();
+    loop {
+        while tasks.len() < workers {
+            let Some(((size, _), (path, copies))) = pending.next() else {
+                break;
+            };
+            let path = path.to_string();
+            tasks.spawn_blocking(move || {
+                let chunks = chunk_file(Path::new(&path), avg_size);
+                (path, size, copies, chunks)
+            });
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        let (path, size, copies, result) = joined.context("Chunk task failed")?;
+        match result {
+            Ok(chunks) => chunked.push(FileChunks {
+                path,
+                size,
+                copies,
+                chunks,
+            }),
+            Err(e) => errors.push(ScanError {
+                path: path.into(),
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+    chunked.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((chunked, errors))
+}
+
+/// Savings of a chunk-level store over `files` and the pairs of files sharing
+/// at least `min_ratio` of the smaller one.
+pub fn analyze(files: &[FileChunks], min_ratio: f64) -> ChunkReport {
+    let mut report = ChunkReport::default();
+
+    // chunk -> (length, files containing it)
+    let mut index: HashMap<u128, (u32, Vec<usize>)> = HashMap::new();
+    for (idx, file) in files.iter().enumerate() {
+        report.files += file.copies;
+        report.bytes += file.size * file.copies;
+        report.file_savings += file.size * (file.copies - 1);
+        for &(hash, length) in &file.chunks {
+            let (_, owners) = index.entry(hash).or_insert((length, Vec::new()));
+            if owners.last() != Some(&idx) {
+                owners.push(idx);
+            }
+        }
+    }
+    report.chunks = index.len() as u64;
+    report.unique_bytes = index.values().map(|(length, _)| *length as u64).sum();
+    report.savings = report.bytes.saturating_sub(report.unique_bytes);
+
+    let mut shared: HashMap<(usize, usize), u64> = HashMap::new();
+    for (length, owners) in index.values() {
+        if owners.len() < 2 || owners.len() > MAX_PAIR_FANOUT {
+            continue;
+        }
+        for (i, &a) in owners.iter().enumerate() {
+            for &b in &owners[i + 1..] {
+                *shared.entry((a, b)).or_default() += *length as u64;
+            }
+        }
+    }
+
+    report.pairs = shared
+        .into_iter()
+        .map(|((a, b), shared_bytes)| {
+            let (a, b) = (&files[a], &files[b]);
+            SharedChunks {
+                a: a.path.clone(),
+                a_size: a.size,
+                b: b.path.clone(),
+                b_size: b.size,
+                shared_bytes,
+                ratio: shared_bytes as f64 / a.size.min(b.size).max(1) as f64,
+            }
+        })
+        .filter(|pair| pair.ratio >= min_ratio)
+        .collect();
+    report.pairs.sort_by(|x, y| {
+        y.shared_bytes
+            .cmp(&x.shared_bytes)
+            .then_with(|| x.a.cmp(&y.a))
+            .then_with(|| x.b.cmp(&y.b))
+    });
+    report
+}
@@ -0,0 +1,79 @@
+use anyhow::{bail, Context, Result};
+use reqwest::{
+    blocking::{Client, ClientBuilder},
+    Certificate, Identity, Proxy,
+};
+use std::{fs, path::PathBuf, time::Duration};
+
+/// HTTP client settings shared by everything that talks WebDAV.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ClientOptions {
+    /// Additional trusted CA bundle (PEM), e.g. a private CA
+    #[arg(long, value_name = "PEM")]
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate and private key in one PEM file
+    #[arg(long, value_name = "PEM")]
+    pub client_cert: Option<PathBuf>,
+    /// HTTP(S) proxy used for all requests
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+    /// Total timeout per request in seconds
+    #[arg(long, value_name = "SECS", value_parser = parse_secs)]
+    pub timeout: Option<Duration>,
+    /// User-Agent header, defaults to `dedup/<version>`
+    #[arg(long, value_name = "UA")]
+    pub user_agent: Option<String>,
+    /// Accept any server certificate, only for local stand-in servers
+    #[arg(long)]
+    pub insecure: bool,
+}
+
+fn parse_secs(s: &str) -> Result<Duration> {
+    let secs: u64 = s.parse().context("expected whole seconds")?;
+    Ok(Duration::from_secs(secs))
+}
+
+impl ClientOptions {
+    pub fn build(&self) -> Result<Client> {
+        let mut builder = ClientBuilder::new().user_agent(
+            self.user_agent
+                .clone()
+                .unwrap_or_else(|| format!("dedup/{}", env!("CARGO_PKG_VERSION"))),
+        );
+
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA bundle {}", path.display()))?;
+            if certs.is_empty() {
+                bail!("No certificates found in {}", path.display());
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(path) = &self.client_cert {
+            let pem = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let identity = Identity::from_pem(&pem)
+                .with_context(|| format!("Invalid client certificate {}", path.display()))?;
+            // PEM identities are only supported by the rustls backend
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if self.insecure {
+            eprintln!("⚠️  --insecure: server certificates are NOT verified");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder.build().context("Failed to build HTTP client")
+    }
+}
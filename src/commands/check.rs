@@ -0,0 +1,71 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::audit::Auditor;
+use dedup::meta;
+use dedup::output;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Duplicate groups to check (JSON, NDJSON or scan database)
+    #[arg(short, long, default_value = "duplicates.json")]
+    input: PathBuf,
+
+    /// Write the findings as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the findings as JSON instead of text
+    #[arg(long)]
+    json: bool,
+
+    /// Only check the file itself, not the files on disk
+    #[arg(long)]
+    no_disk: bool,
+
+    /// Source label of this machine in merged inventories [default: host name]
+    #[arg(long)]
+    source: Option<String>,
+}
+
+/// Exit code when the audit found problems.
+const FINDINGS_EXIT_CODE: i32 = 2;
+
+pub fn run(args: CheckArgs, verbosity: Verbosity) -> Result<()> {
+    let local_source = args.source.clone().unwrap_or_else(meta::local_hostname);
+    let mut auditor = Auditor::new(!args.no_disk, Some(local_source));
+
+    super::for_each_group(&args.input, |group| {
+        auditor.add_group(&group);
+        Ok(())
+    })?;
+    let report = auditor.finish();
+
+    if let Some(path) = &args.output {
+        output::write_json_atomic(path, &report)?;
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for finding in &report.findings {
+            println!("⚠️  {}", finding);
+        }
+        if !verbosity.quiet() {
+            println!(
+                "{} group(s) checked, {} file(s) compared with disk, {} finding(s).",
+                report.groups,
+                report.files_checked,
+                report.findings.len()
+            );
+        }
+    }
+
+    if !report.findings.is_empty() {
+        std::process::exit(FINDINGS_EXIT_CODE);
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::chunks::{self, DEFAULT_CHUNK_SIZE, DEFAULT_MIN_FILE_SIZE, DEFAULT_MIN_RATIO};
+use dedup::output;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct ChunksArgs {
+    /// Inventory (all_files.json, .ndjson or scan database) of a local scan
+    #[arg(short, long, default_value = "all_files.json")]
+    input: PathBuf,
+
+    /// Also write the savings and all reported pairs as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Only chunk files of at least this many bytes
+    #[arg(long, default_value_t = DEFAULT_MIN_FILE_SIZE)]
+    min_size: u64,
+
+    /// Average chunk size in bytes
+    #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE,
+          value_parser = clap::value_parser!(u32).range(1024..=4_194_304))]
+    chunk_size: u32,
+
+    /// Share of the smaller file, from 0 to 1, two files must have in common
+    /// to be reported
+    #[arg(long, default_value_t = DEFAULT_MIN_RATIO)]
+    min_ratio: f64,
+
+    /// Print at most this many pairs, 0 for all
+    #[arg(short = 'l', long, default_value_t = 20)]
+    limit: usize,
+}
+
+pub async fn run(args: ChunksArgs, verbosity: Verbosity) -> Result<()> {
+    let files = super::read_files(&args.input)?;
+    let (chunked, errors) = chunks::chunk_files(&files, args.min_size, args.chunk_size).await?;
+    for error in &errors {
+        eprintln!("Error chunking file {}: {}", error.path.display(), error.message);
+    }
+    let report = chunks::analyze(&chunked, args.min_ratio);
+
+    if let Some(path) = &args.output {
+        output::write_json_atomic(path, &report)?;
+    }
+
+    if verbosity.quiet() {
+        return Ok(());
+    }
+
+    let limit = if args.limit == 0 { usize::MAX } else { args.limit };
+    for pair in report.pairs.iter().take(limit) {
+        println!(
+            "{:.0}% shared, {:.2} MB",
+            pair.ratio * 100.0,
+            pair.shared_bytes as f64 / 1_048_576.0
+        );
+        println!("  {} ({:.2} MB)", pair.a, pair.a_size as f64 / 1_048_576.0);
+        println!("  {} ({:.2} MB)\n", pair.b, pair.b_size as f64 / 1_048_576.0);
+    }
+
+    println!("=== Statistics ===");
+    println!("Chunked files         : {}", report.files);
+    println!(
+        "Total data processed  : {:.2} MB",
+        report.bytes as f64 / 1_048_576.0
+    );
+    println!("Distinct chunks       : {}", report.chunks);
+    println!("Similar file pairs    : {}", report.pairs.len());
+    println!(
+        "Chunk-level savings   : {:.2} MB ({:.2} MB from identical files)",
+        report.savings as f64 / 1_048_576.0,
+        report.file_savings as f64 / 1_048_576.0
+    );
+    if let Some(path) = &args.output {
+        println!("\nOutput written to:\n  {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,485 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use dedup::archive;
+use dedup::audit;
+use dedup::dirs::{self, DirReport};
+use dedup::meta;
+use dedup::similar;
+use dedup::types::FileEntry;
+
+use super::overlap;
+use super::{InputCheckArgs, Verbosity};
+
+#[derive(Args)]
+pub struct CleanArgs {
+    /// Duplicate groups to clean up (JSON, NDJSON or scan database)
+    #[arg(short, long, default_value = "duplicates.json")]
+    input: PathBuf,
+
+    /// Only show what would be deleted (the default)
+    #[arg(short = 'n', long, overrides_with = "no_dry_run")]
+    dry_run: bool,
+
+    /// Really delete files
+    #[arg(long, overrides_with = "dry_run")]
+    no_dry_run: bool,
+
+    /// First decide which folder to keep for the N folder pairs sharing the
+    /// most duplicates, then go through the remaining groups
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    folders: usize,
+
+    /// Remove whole directories from the report of `dedup dirs -o` instead
+    /// of going through the groups; needs --inventory
+    #[arg(long, value_name = "FILE", requires = "inventory", conflicts_with = "folders")]
+    dirs: Option<PathBuf>,
+
+    /// Inventory of the same scan, so --folders shows the share of all files
+    /// in a folder instead of only its duplicates and --dirs knows the files
+    /// of a directory
+    #[arg(long, value_name = "FILE")]
+    inventory: Option<PathBuf>,
+
+    /// Source label of this machine in merged inventories [default: host name];
+    /// files of other sources are never deleted
+    #[arg(long)]
+    source: Option<String>,
+
+    #[command(flatten)]
+    input_check: InputCheckArgs,
+}
+
+pub fn run(args: CleanArgs, verbosity: Verbosity) -> Result<()> {
+    let json_path = &args.input;
+    let dry_run = !args.no_dry_run;
+    let local_source = args.source.clone().unwrap_or_else(meta::local_hostname);
+
+    if let (Some(dirs_path), Some(inventory)) = (&args.dirs, &args.inventory) {
+        args.input_check.verify(inventory)?;
+        let file = File::open(dirs_path).with_context(|| format!("Failed to open {}", dirs_path.display()))?;
+        let report: DirReport = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to parse {}", dirs_path.display()))?;
+        let mut files = super::read_files(inventory)?;
+        files.retain(|f| audit::is_local(f, Some(&local_source)));
+        return clean_dirs(&report, &files, dry_run);
+    }
+
+    args.input_check.verify(json_path)?;
+    let mut groups: Vec<Vec<FileEntry>> = super::read_groups(json_path)?;
+    if groups.iter().any(|g| similar::is_similar_group(g)) {
+        bail!(
+            "{} holds similar groups, which are not byte-identical; use remote-clean --policy best-quality",
+            json_path.display()
+        );
+    }
+
+    // merged inventories hold paths of other machines, which may name
+    // different files here
+    let mut foreign = 0usize;
+    for group in &mut groups {
+        let before = group.len();
+        group.retain(|f| audit::is_local(f, Some(&local_source)));
+        foreign += before - group.len();
+    }
+
+    println!("Loaded {} duplicate groups\n", groups.len());
+    if foreign > 0 {
+        println!("Ignoring {} file(s) of other sources than {}\n", foreign, local_source);
+    }
+    if dry_run {
+        println!("*** DRY-RUN MODE: no files will be deleted ***\n");
+    }
+
+    let mut preferred_dirs: HashSet<PathBuf> = HashSet::new();
+    let mut processed_groups = vec![false; groups.len()];
+
+    // ---- folder-level decisions up front, as (kept, dropped) folder pairs
+    if args.folders > 0 {
+        let inventory = args.inventory.as_deref().map(super::read_files).transpose()?;
+        let overlaps = dirs::overlaps(&groups, inventory.as_deref());
+        let count = overlaps.len().min(args.folders);
+        let mut decisions: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for (i, pair) in overlaps.iter().take(count).enumerate() {
+            print!("Folder pair {}/{}: ", i + 1, count);
+            overlap::print_overlap(pair);
+            let choice = ask_choice("Which folder should be kept?", "pair", 2)?;
+            if choice == 0 {
+                println!("Pair skipped.\n");
+            } else if choice == usize::MAX {
+                println!("Cancel requested. Exiting.");
+                return Ok(());
+            } else {
+                let kept = PathBuf::from(&pair.folders[choice - 1].dir);
+                let dropped = PathBuf::from(&pair.folders[2 - choice].dir);
+                println!("Keeping\n  {}\nover\n  {}\n", kept.display(), dropped.display());
+                decisions.push((kept, dropped));
+            }
+        }
+
+        // a decision only removes the copies in the dropped folder of groups
+        // that also have a copy in the kept one; copies elsewhere stay for the
+        // per-group questions below
+        for (kept, dropped) in &decisions {
+            for (idx, group) in groups.iter_mut().enumerate() {
+                if processed_groups[idx] {
+                    continue;
+                }
+                let in_folder = |f: &FileEntry, dir: &Path| Path::new(&f.path).parent() == Some(dir);
+                if !group.iter().any(|f| in_folder(f, kept)) || !group.iter().any(|f| in_folder(f, dropped)) {
+                    continue;
+                }
+
+                let mut deleted = 0usize;
+                group.retain(|file| {
+                    if !in_folder(file, dropped) {
+                        return true;
+                    }
+                    if archive::is_member(&file.path) {
+                        println!("Keeping archive member (read-only): {}", file.path);
+                        return true;
+                    }
+                    if remove_file(Path::new(&file.path), dry_run) {
+                        deleted += 1;
+                        false
+                    } else {
+                        true
+                    }
+                });
+                if deleted > 0 || dry_run {
+                    println!(
+                        "Group #{} finished, {} file(s) {}.\n",
+                        idx + 1,
+                        deleted,
+                        if dry_run { "would be deleted" } else { "deleted" }
+                    );
+                }
+                // nothing left to decide once all copies share a folder
+                let mut remaining = group.iter().map(|f| Path::new(&f.path).parent());
+                let first = remaining.next();
+                if remaining.all(|dir| dir == first.flatten()) {
+                    processed_groups[idx] = true;
+                }
+            }
+        }
+    }
+
+    for group_index in 0..groups.len() {
+        if processed_groups[group_index] {
+            continue;
+        }
+
+        let group = &groups[group_index];
+        if group.is_empty() {
+            processed_groups[group_index] = true;
+            continue;
+        }
+
+        // Collect all parent directories in this group
+        let mut dirs_in_group: HashSet<PathBuf> = HashSet::new();
+        for file in group {
+            if let Some(parent) = PathBuf::from(&file.path).parent() {
+                dirs_in_group.insert(parent.to_path_buf());
+            }
+        }
+
+        // If all files are in the same directory, skip this group
+        if dirs_in_group.len() == 1 {
+            if !verbosity.quiet() {
+                println!(
+                    "Skipping group #{}: all files in same directory ({})\n",
+                    group_index + 1,
+                    dirs_in_group.iter().next().unwrap().display()
+                );
+            }
+            processed_groups[group_index] = true;
+            continue;
+        }
+
+        println!(
+            "Duplicate group #{} ({} files, multiple directories)",
+            group_index + 1,
+            group.len()
+        );
+
+        // Check for already preferred dirs
+        let mut matching_dirs = Vec::new();
+        for dir in &dirs_in_group {
+            if preferred_dirs.contains(dir) {
+                matching_dirs.push(dir.clone());
+            }
+        }
+
+        let keep_dir_opt: Option<PathBuf> = if matching_dirs.len() == 1 {
+            let dir = matching_dirs[0].clone();
+            println!(
+                "Using preferred directory automatically:\n  {}\n",
+                dir.display()
+            );
+            Some(dir)
+        } else {
+            // Ask user
+            for (i, file) in group.iter().enumerate() {
+                println!("[{}] {}", i + 1, file.path);
+            }
+
+            let choice = ask_choice(
+                "Which file should define the preferred directory?",
+                "group",
+                group.len(),
+            )?;
+            if choice == 0 {
+                println!("Group skipped.\n");
+                None
+            } else if choice == usize::MAX {
+                println!("Cancel requested. Exiting.");
+                return Ok(());
+            } else {
+                let chosen_path = PathBuf::from(&group[choice - 1].path);
+                let dir = chosen_path
+                    .parent()
+                    .context("Failed to determine parent directory")?
+                    .to_path_buf();
+
+                println!(
+                    "Selected preferred directory:\n  {}\n",
+                    dir.display()
+                );
+                preferred_dirs.insert(dir.clone());
+                Some(dir)
+            }
+        };
+
+        if let Some(keep_dir) = keep_dir_opt {
+            // Process all groups containing files in this directory
+            for (idx, grp) in groups.iter().enumerate() {
+                if processed_groups[idx] {
+                    continue;
+                }
+
+                let has_in_keep_dir = grp.iter().any(|f| {
+                    let path = PathBuf::from(&f.path);
+                    path.starts_with(&keep_dir)
+                });
+                // copies in another preferred directory are asked about, not
+                // deleted, unless this is the group just answered
+                let has_in_other_preferred = idx != group_index && grp.iter().any(|f| {
+                    let path = PathBuf::from(&f.path);
+                    preferred_dirs
+                        .iter()
+                        .any(|dir| *dir != keep_dir && path.starts_with(dir) && !path.starts_with(&keep_dir))
+                });
+
+                if has_in_keep_dir && !has_in_other_preferred {
+                    processed_groups[idx] = true;
+                    let mut deleted = 0usize;
+                    for file in grp {
+                        let file_path = PathBuf::from(&file.path);
+                        if file_path.starts_with(&keep_dir) {
+                            continue;
+                        }
+                        if archive::is_member(&file.path) {
+                            println!("Keeping archive member (read-only): {}", file.path);
+                            continue;
+                        }
+
+                        if remove_file(&file_path, dry_run) {
+                            deleted += 1;
+                        }
+                    }
+                    if deleted > 0 || dry_run {
+                        println!(
+                            "Group #{} finished, {} file(s) {}.\n",
+                            idx + 1,
+                            deleted,
+                            if dry_run { "would be deleted" } else { "deleted" }
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    println!("All duplicate groups processed.");
+    Ok(())
+}
+
+/// Go through the identical and contained directories of a `dedup dirs`
+/// report and remove the ones the user drops, one decision per directory.
+///
+/// Only the files of a dropped directory listed in `inventory` are deleted,
+/// then the directories left empty. Files the scan skipped, e.g. below its
+/// `--min-size`, were never compared; they and their directories stay.
+fn clean_dirs(report: &DirReport, inventory: &[FileEntry], dry_run: bool) -> Result<()> {
+    println!(
+        "Loaded {} identical directory set(s) and {} contained directories\n",
+        report.identical.len(),
+        report.subsets.len()
+    );
+    if dry_run {
+        println!("*** DRY-RUN MODE: no files will be deleted ***\n");
+    }
+    if report.scan_min_size > 0 {
+        println!(
+            "\x1b[33m⚠️  Files below {} bytes were not compared, they are left in place with their directories\x1b[0m\n",
+            report.scan_min_size
+        );
+    }
+
+    // a decision must not rely on a directory that an earlier one removed or
+    // removed something from
+    let mut dropped: Vec<String> = Vec::new();
+    let is_dropped = |dropped: &[String], dir: &str| {
+        dropped.iter().any(|d| d == dir || dirs::is_below(dir, d) || dirs::is_below(d, dir))
+    };
+
+    for (i, set) in report.identical.iter().enumerate() {
+        let candidates: Vec<&String> = set.dirs.iter().filter(|d| !is_dropped(&dropped, d)).collect();
+        if candidates.len() < 2 {
+            continue;
+        }
+        println!(
+            "Identical directories {}/{}: {} files, {:.2} MB each",
+            i + 1,
+            report.identical.len(),
+            set.files,
+            set.bytes as f64 / 1_048_576.0
+        );
+        for (n, dir) in candidates.iter().enumerate() {
+            println!("[{}] {}", n + 1, dir);
+        }
+        let choice = ask_choice("Which directory should be kept?", "set", candidates.len())?;
+        if choice == 0 {
+            println!("Set skipped.\n");
+            continue;
+        } else if choice == usize::MAX {
+            println!("Cancel requested. Exiting.");
+            return Ok(());
+        }
+        for (n, dir) in candidates.iter().enumerate() {
+            if n + 1 != choice {
+                remove_dir_files(dir, inventory, dry_run);
+                dropped.push(dir.to_string());
+            }
+        }
+        println!();
+    }
+
+    for (i, subset) in report.subsets.iter().enumerate() {
+        if is_dropped(&dropped, &subset.dir) || is_dropped(&dropped, &subset.superset) {
+            continue;
+        }
+        println!("Contained directory {}/{}:", i + 1, report.subsets.len());
+        println!(
+            "[1] {} ({} files, {:.2} MB)",
+            subset.dir,
+            subset.files,
+            subset.bytes as f64 / 1_048_576.0
+        );
+        println!(
+            "    is part of {} ({} files, {:.2} MB)",
+            subset.superset,
+            subset.superset_files,
+            subset.superset_bytes as f64 / 1_048_576.0
+        );
+        let choice = ask_choice("Remove [1]?", "directory", 1)?;
+        if choice == 0 {
+            println!("Directory skipped.\n");
+            continue;
+        } else if choice == usize::MAX {
+            println!("Cancel requested. Exiting.");
+            return Ok(());
+        }
+        remove_dir_files(&subset.dir, inventory, dry_run);
+        dropped.push(subset.dir.clone());
+        println!();
+    }
+
+    println!("All directories processed.");
+    Ok(())
+}
+
+/// Delete the files of `inventory` below `dir`, then the directories that
+/// became empty, deepest first.
+fn remove_dir_files(dir: &str, inventory: &[FileEntry], dry_run: bool) {
+    let mut deleted = 0usize;
+    let mut parents: BTreeSet<&Path> = BTreeSet::new();
+    for file in inventory.iter().filter(|f| dirs::is_below(&f.path, dir)) {
+        if archive::is_member(&file.path) {
+            println!("Keeping archive member (read-only): {}", file.path);
+            continue;
+        }
+        let path = Path::new(&file.path);
+        if remove_file(path, dry_run) {
+            deleted += 1;
+            parents.extend(path.ancestors().skip(1).take_while(|p| p.starts_with(dir)));
+        }
+    }
+    if dry_run {
+        println!("{} file(s) below {} would be deleted.", deleted, dir);
+        return;
+    }
+    // BTreeSet order puts parents first, so go backwards
+    for parent in parents.iter().rev() {
+        let _ = fs::remove_dir(parent);
+    }
+    if Path::new(dir).exists() {
+        println!("{} file(s) deleted, {} still holds files that were not compared.", deleted, dir);
+    } else {
+        println!("{} file(s) deleted, removed {}.", deleted, dir);
+    }
+}
+
+/// Delete `path`, or only say so in a dry run. Returns whether it is gone.
+fn remove_file(path: &Path, dry_run: bool) -> bool {
+    if dry_run {
+        println!("Would delete: {}", path.display());
+        return true;
+    }
+    match fs::remove_file(path) {
+        Ok(_) => {
+            println!("Deleted: {}", path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to delete {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+fn ask_choice(question: &str, skip: &str, max: usize) -> Result<usize> {
+    loop {
+        print!(
+            "{} Enter 1-{}, s=skip {}, c=cancel all: ",
+            question, max, skip
+        );
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(usize::MAX); // stdin closed, treat as cancel
+        }
+        let input = input.trim().to_lowercase();
+
+        match input.as_str() {
+            "s" => return Ok(0),
+            "c" => return Ok(usize::MAX),
+            _ => {
+                if let Ok(num) = input.parse::<usize>() {
+                    if num >= 1 && num <= max {
+                        return Ok(num);
+                    }
+                }
+            }
+        }
+
+        println!("Invalid input, please try again.\n");
+    }
+}
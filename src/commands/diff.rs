@@ -0,0 +1,113 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+use dedup::diff;
+use dedup::output;
+use dedup::records;
+use dedup::store;
+
+use super::Verbosity;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Older inventory (all_files.json, .ndjson or scan database)
+    old: PathBuf,
+
+    /// Newer inventory
+    new: PathBuf,
+
+    /// Also write the changes as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: DiffArgs, verbosity: Verbosity) -> Result<()> {
+    // databases don't record the algorithm
+    let hash_algorithm = |path: &Path| -> Result<Option<String>> {
+        if store::is_database(path) {
+            return Ok(None);
+        }
+        Ok(records::read_meta(path)?.map(|meta| meta.hash_algorithm))
+    };
+    let old_algorithm = hash_algorithm(&args.old)?;
+    let new_algorithm = hash_algorithm(&args.new)?;
+
+    let old = super::read_files(&args.old)?;
+    let new = super::read_files(&args.new)?;
+    let changes = diff::diff(&old, &new);
+
+    if let Some(path) = &args.output {
+        output::write_json_atomic(path, &changes)?;
+    }
+
+    if verbosity.quiet() {
+        return Ok(());
+    }
+
+    // list every change with -v, otherwise only the counts
+    if verbosity.verbose() {
+        for f in &changes.added {
+            println!("{GREEN}+ {}{RESET}", f.path);
+        }
+        for f in &changes.deleted {
+            println!("{RED}- {}{RESET}", f.path);
+        }
+        for m in &changes.modified {
+            println!("{YELLOW}~ {} ({} -> {} bytes){RESET}", m.path, m.old_size, m.new_size);
+        }
+        for m in &changes.moved {
+            println!("> {} -> {}", m.from, m.to);
+        }
+        println!();
+    }
+
+    let summary = &changes.summary;
+    println!("=== {} -> {} ===", args.old.display(), args.new.display());
+    println!(
+        "Added                 : {} ({:.2} MB)",
+        summary.added,
+        summary.added_bytes as f64 / 1_048_576.0
+    );
+    println!(
+        "Deleted               : {} ({:.2} MB)",
+        summary.deleted,
+        summary.deleted_bytes as f64 / 1_048_576.0
+    );
+    println!("Modified              : {}", summary.modified);
+    println!("Moved                 : {}", summary.moved);
+    if summary.unverified > 0 {
+        println!("Unverified            : {}", summary.unverified);
+    }
+
+    if let (Some(old), Some(new)) = (&old_algorithm, &new_algorithm) {
+        if old != new {
+            println!(
+                "\n{YELLOW}⚠️  The scans used different hash algorithms ({} and {}), moves are only found by shared ones{RESET}",
+                old, new
+            );
+        }
+    }
+    if summary.unverified > 0 {
+        println!(
+            "\n{YELLOW}⚠️  {} file(s) have no hash by a common algorithm, rescan with a shared --hash to compare them{RESET}",
+            summary.unverified
+        );
+    }
+
+    if summary.modified > 0 {
+        println!(
+            "\n{YELLOW}⚠️  {} file(s) changed content in place, check them if the archive should be immutable{RESET}",
+            summary.modified
+        );
+    }
+    if let Some(path) = &args.output {
+        println!("\nOutput written to:\n  {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::dirs;
+use dedup::output;
+use dedup::records;
+use dedup::store;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct DirsArgs {
+    /// Inventory (all_files.json, .ndjson or scan database); duplicates.json
+    /// is not enough, directories are compared by all their files
+    #[arg(short, long, default_value = "all_files.json")]
+    input: PathBuf,
+
+    /// Also write the identical and contained directories as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Ignore directories with fewer bytes than this
+    #[arg(long, default_value_t = 1_048_576)]
+    min_size: u64,
+
+    /// Print at most this many entries per section, 0 for all
+    #[arg(short = 'l', long, default_value_t = 20)]
+    limit: usize,
+}
+
+pub fn run(args: DirsArgs, verbosity: Verbosity) -> Result<()> {
+    let files = super::read_files(&args.input)?;
+    let mut report = dirs::analyze(&files);
+    report.identical.retain(|i| i.bytes >= args.min_size);
+    report.subsets.retain(|s| s.bytes >= args.min_size);
+    // databases don't record the scan options
+    if !store::is_database(&args.input) {
+        report.scan_min_size = records::read_meta(&args.input)?.map_or(0, |m| m.options.min_size);
+    }
+
+    if let Some(path) = &args.output {
+        output::write_json_atomic(path, &report)?;
+    }
+
+    if verbosity.quiet() {
+        return Ok(());
+    }
+
+    let small_files_warning = || {
+        if report.scan_min_size > 0 {
+            println!(
+                "\x1b[33m⚠️  Files below {} bytes were not compared, check for them before removing a directory\x1b[0m\n",
+                report.scan_min_size
+            );
+        }
+    };
+    if report.scan_min_size > 0 {
+        println!(
+            "ℹ️  The scan skipped files below {} bytes, directories are compared without them\n",
+            report.scan_min_size
+        );
+    }
+
+    let limit = if args.limit == 0 { usize::MAX } else { args.limit };
+    if !report.identical.is_empty() {
+        println!("📁 Identical directories\n");
+        small_files_warning();
+        for identical in report.identical.iter().take(limit) {
+            println!(
+                "{} copies of {} files ({:.2} MB each)",
+                identical.dirs.len(),
+                identical.files,
+                identical.bytes as f64 / 1_048_576.0
+            );
+            for dir in &identical.dirs {
+                println!("  {}", dir);
+            }
+            println!();
+        }
+    }
+    if !report.subsets.is_empty() {
+        println!("📂 Directories contained in others\n");
+        small_files_warning();
+        for subset in report.subsets.iter().take(limit) {
+            println!(
+                "{} ({} files, {:.2} MB)",
+                subset.dir,
+                subset.files,
+                subset.bytes as f64 / 1_048_576.0
+            );
+            println!(
+                "  is part of {} ({} files, {:.2} MB)\n",
+                subset.superset,
+                subset.superset_files,
+                subset.superset_bytes as f64 / 1_048_576.0
+            );
+        }
+    }
+
+    println!("=== Statistics ===");
+    println!("Identical dir sets    : {}", report.identical.len());
+    println!("Contained dirs        : {}", report.subsets.len());
+    println!(
+        "Redundant copies      : {:.2} MB",
+        report.identical.iter().map(|i| i.redundant_bytes()).sum::<u64>() as f64 / 1_048_576.0
+    );
+    if let Some(path) = &args.output {
+        println!("\nOutput written to:\n  {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::group::{self, DEFAULT_MEMORY_BUDGET};
+use dedup::records::{self, RecordWriter};
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct GroupArgs {
+    /// Inventory to group, all_files.json or all_files.ndjson
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Duplicate groups, .ndjson/.jsonl for NDJSON, JSON otherwise
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Memory to use for grouping, in MB; larger inventories are grouped
+    /// in buckets on disk
+    #[arg(long, default_value_t = DEFAULT_MEMORY_BUDGET / 1_048_576)]
+    memory_budget: u64,
+}
+
+pub fn run(args: GroupArgs, verbosity: Verbosity) -> Result<()> {
+    let meta = records::read_meta(&args.input)?;
+    let mut writer = RecordWriter::create(&args.output, meta.as_ref())?;
+    let stats = group::group_file(
+        &args.input,
+        args.memory_budget.saturating_mul(1_048_576),
+        |group| writer.write(&group),
+    )?;
+    writer.finish()?;
+
+    if !verbosity.quiet() {
+        println!(
+            "{} duplicate group(s), {} files, {:.2} MB potential savings, {} written.",
+            stats.duplicate_groups,
+            stats.duplicate_files,
+            stats.potential_savings as f64 / 1_048_576.0,
+            args.output.display()
+        );
+    }
+    Ok(())
+}
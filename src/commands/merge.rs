@@ -0,0 +1,170 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use dedup::group::{self, DEFAULT_MEMORY_BUDGET};
+use dedup::meta::{self, ScanMeta, ScanSettings};
+use dedup::output::OutputLocation;
+use dedup::records::{self, Format, RecordWriter};
+use dedup::types::FileEntry;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Inventories as LABEL=PATH, e.g. nas=nas/all_files.json; without a
+    /// label the host name recorded in the file is used
+    #[arg(required = true, num_args = 2.., value_name = "[LABEL=]PATH")]
+    inputs: Vec<String>,
+
+    /// Directory for the merged all_files and duplicates files
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Prefix for the result file names
+    #[arg(long, default_value = "merged-")]
+    prefix: String,
+
+    /// Format of the result files
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Memory for grouping, in MB
+    #[arg(long, default_value_t = DEFAULT_MEMORY_BUDGET / 1_048_576)]
+    memory_budget: u64,
+}
+
+struct Input {
+    label: String,
+    path: PathBuf,
+    meta: Option<ScanMeta>,
+}
+
+pub fn run(args: MergeArgs, verbosity: Verbosity) -> Result<()> {
+    let started_at = meta::now();
+    let inputs = args
+        .inputs
+        .iter()
+        .map(|arg| parse_input(arg))
+        .collect::<Result<Vec<_>>>()?;
+
+    let labels: BTreeSet<&str> = inputs.iter().map(|i| i.label.as_str()).collect();
+    if labels.len() != inputs.len() {
+        bail!("labels must be unique, name the inputs with LABEL=PATH");
+    }
+
+    // files hashed differently never end up in the same group
+    let algorithms: BTreeSet<&str> = inputs
+        .iter()
+        .filter_map(|i| i.meta.as_ref().map(|m| m.hash_algorithm.as_str()))
+        .collect();
+    if algorithms.len() > 1 {
+        eprintln!(
+            "\x1b[33m⚠️  inputs use different hash algorithms ({}), only files with matching checksums are grouped\x1b[0m",
+            algorithms.iter().copied().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    let location = OutputLocation::new(&args.output_dir, args.prefix.as_str());
+    location.create_dir()?;
+    let ext = args.format.extension();
+    let all_files_path = location.path(&format!("all_files.{}", ext));
+    let duplicates_path = location.path(&format!("duplicates.{}", ext));
+
+    let merged_meta = merged_meta(&inputs, started_at, algorithms.into_iter().collect());
+
+    // ---- combine, tagging every file with its label
+    let mut writer = RecordWriter::create(&all_files_path, Some(&merged_meta))?;
+    for input in &inputs {
+        let before = writer.count();
+        records::for_each(&input.path, |mut entry: FileEntry| {
+            entry.source = Some(input.label.clone());
+            writer.write(&entry)
+        })?;
+        if !verbosity.quiet() {
+            println!("{:<12} {:>10} files  {}", input.label, writer.count() - before, input.path.display());
+        }
+    }
+    writer.finish()?;
+
+    // ---- find duplicates across all sources
+    let mut writer = RecordWriter::create(&duplicates_path, Some(&merged_meta))?;
+    let mut cross_source = 0u64;
+    let stats = group::group_file(
+        &all_files_path,
+        args.memory_budget.saturating_mul(1_048_576),
+        |group| {
+            let sources: BTreeSet<_> = group.iter().map(|f| &f.source).collect();
+            if sources.len() > 1 {
+                cross_source += 1;
+            }
+            writer.write(&group)
+        },
+    )?;
+    writer.finish()?;
+
+    println!("\n=== Statistics ===");
+    println!("Sources               : {}", inputs.len());
+    println!("Duplicate files       : {}", stats.duplicate_files);
+    println!("Duplicate groups      : {}", stats.duplicate_groups);
+    println!("  across sources      : {}", cross_source);
+    println!(
+        "Potential savings     : {:.2} MB",
+        stats.potential_savings as f64 / 1_048_576.0
+    );
+
+    println!("\nOutput written to:");
+    println!("  {}", all_files_path.display());
+    println!("  {}", duplicates_path.display());
+
+    Ok(())
+}
+
+fn parse_input(arg: &str) -> Result<Input> {
+    let (label, path) = match arg.split_once('=') {
+        Some((label, path)) if !label.is_empty() => (Some(label.to_string()), PathBuf::from(path)),
+        _ => (None, PathBuf::from(arg)),
+    };
+    let meta = records::read_meta(&path)?;
+
+    let label = match (label, &meta) {
+        (Some(label), _) => label,
+        (None, Some(meta)) => meta.hostname.clone(),
+        (None, None) => bail!("{} has no scan metadata, give it a label: LABEL={}", path.display(), arg),
+    };
+
+    Ok(Input { label, path, meta })
+}
+
+/// Metadata of the merged inventory: `label:root` roots, the labels as host
+/// name and the oldest scan's start and finish time.
+fn merged_meta(inputs: &[Input], started_at: chrono::DateTime<chrono::FixedOffset>, algorithms: Vec<&str>) -> ScanMeta {
+    let mut roots = Vec::new();
+    for input in inputs {
+        match &input.meta {
+            Some(meta) => roots.extend(meta.roots.iter().map(|r| format!("{}:{}", input.label, r))),
+            None => roots.push(format!("{}:{}", input.label, input.path.display())),
+        }
+    }
+
+    let hash_algorithm = if algorithms.is_empty() {
+        "unknown".to_string()
+    } else {
+        algorithms.join("+")
+    };
+    let metas = || inputs.iter().filter_map(|i| i.meta.as_ref());
+    let settings = ScanSettings {
+        min_size: metas().map(|m| m.options.min_size).min().unwrap_or_default(),
+        archives: metas().any(|m| m.options.archives),
+        ..ScanSettings::default()
+    };
+    let mut merged = ScanMeta::new(roots, settings, &hash_algorithm, started_at);
+
+    if let Some(oldest) = metas().min_by_key(|m| m.finished_at) {
+        merged.started_at = oldest.started_at;
+        merged.finished_at = oldest.finished_at;
+    }
+    merged.hostname = inputs.iter().map(|i| i.label.as_str()).collect::<Vec<_>>().join(",");
+    merged
+}
@@ -0,0 +1,152 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+use dedup::credentials::{self, Credentials};
+use dedup::meta;
+use dedup::records;
+use dedup::store::{self, Store};
+use dedup::types::FileEntry;
+
+pub mod check;
+pub mod chunks;
+pub mod clean;
+pub mod diff;
+pub mod dirs;
+pub mod group;
+pub mod merge;
+pub mod overlap;
+pub mod query;
+pub mod remote_clean;
+pub mod report;
+pub mod scan;
+pub mod similar;
+
+/// Output level from `-q` / `-v`.
+#[derive(Debug, Clone, Copy)]
+pub struct Verbosity(i8);
+
+impl Verbosity {
+    pub fn new(verbose: u8, quiet: bool) -> Self {
+        if quiet {
+            Verbosity(-1)
+        } else {
+            Verbosity(verbose.min(i8::MAX as u8) as i8)
+        }
+    }
+
+    pub fn quiet(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn verbose(self) -> bool {
+        self.0 > 0
+    }
+}
+
+/// WebDAV login, see [`credentials::resolve`] for the lookup order.
+#[derive(Args)]
+pub struct CredentialArgs {
+    /// WebDAV user [default: $DEDUP_WEBDAV_USER, credentials file, ~/.netrc or prompt]
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// WebDAV app password, ends up in shell history and `ps`, prefer the other sources
+    #[arg(long)]
+    pub password: Option<String>,
+
+    /// File with `user = …` and `password = …` lines, must not be readable by others
+    #[arg(long, value_name = "PATH")]
+    pub credentials_file: Option<PathBuf>,
+}
+
+impl CredentialArgs {
+    pub fn resolve(&self, url: &str) -> Result<Credentials> {
+        let host = reqwest::Url::parse(url)
+            .with_context(|| format!("Invalid URL {}", url))?
+            .host_str()
+            .unwrap_or_default()
+            .to_string();
+        credentials::resolve(
+            &host,
+            self.user.as_deref(),
+            self.password.as_deref(),
+            self.credentials_file.as_deref(),
+        )
+    }
+}
+
+/// Safety checks on the scan metadata of a cleaner's input.
+#[derive(Args)]
+pub struct InputCheckArgs {
+    /// Warn about inputs from scans older than this, e.g. 12h, 7d or 2w
+    #[arg(long, value_name = "AGE", default_value = "7d", value_parser = meta::parse_age)]
+    max_age: std::time::Duration,
+
+    /// Refuse inputs that are too old, from another host or without metadata
+    #[arg(long)]
+    strict: bool,
+}
+
+impl InputCheckArgs {
+    /// Warn about (or with `--strict` refuse) a questionable `input`.
+    /// Databases are checked against their most recently finished run.
+    pub fn verify(&self, input: &Path) -> Result<()> {
+        let concerns = if store::is_database(input) {
+            match Store::open_existing(input)?.last_run()? {
+                Some(run) => run.concerns(self.max_age),
+                None => vec!["no finished scan run recorded".to_string()],
+            }
+        } else {
+            match records::read_meta(input)? {
+                Some(meta) => meta.concerns(self.max_age),
+                None => vec!["no scan metadata, it was written by an older dedup".to_string()],
+            }
+        };
+        if concerns.is_empty() {
+            return Ok(());
+        }
+
+        if self.strict {
+            bail!("refusing {}: {}", input.display(), concerns.join("; "));
+        }
+        for concern in &concerns {
+            eprintln!("\x1b[33m⚠️  {}: {}\x1b[0m", input.display(), concern);
+        }
+        Ok(())
+    }
+}
+
+/// Call `f` for every duplicate group in `input`, a JSON/NDJSON file or a
+/// database written by `scan --db`.
+pub fn for_each_group(input: &Path, f: impl FnMut(Vec<FileEntry>) -> Result<()>) -> Result<()> {
+    if store::is_database(input) {
+        Store::open_existing(input)?.for_each_group(f)
+    } else {
+        records::for_each(input, f)
+    }
+}
+
+/// All duplicate groups in `input`, see [`for_each_group`].
+pub fn read_groups(input: &Path) -> Result<Vec<Vec<FileEntry>>> {
+    let mut groups = Vec::new();
+    for_each_group(input, |group| {
+        groups.push(group);
+        Ok(())
+    })?;
+    Ok(groups)
+}
+
+/// All files in an inventory or database.
+pub fn read_files(input: &Path) -> Result<Vec<FileEntry>> {
+    if store::is_database(input) {
+        let mut files = Vec::new();
+        Store::open_existing(input)?.for_each_file(|file| {
+            files.push(file);
+            Ok(())
+        })?;
+        Ok(files)
+    } else {
+        records::read_all(input)
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::dirs::{self, FolderOverlap};
+use dedup::output;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct OverlapArgs {
+    /// Duplicate groups (JSON, NDJSON or scan database)
+    #[arg(short, long, default_value = "duplicates.json")]
+    input: PathBuf,
+
+    /// Inventory of the same scan, so percentages are of all files in a
+    /// folder instead of only its duplicates
+    #[arg(long, value_name = "FILE")]
+    inventory: Option<PathBuf>,
+
+    /// Also write all folder pairs as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print at most this many folder pairs, 0 for all
+    #[arg(short = 'l', long, default_value_t = 20)]
+    limit: usize,
+}
+
+pub fn run(args: OverlapArgs, verbosity: Verbosity) -> Result<()> {
+    let groups = super::read_groups(&args.input)?;
+    let inventory = args.inventory.as_deref().map(super::read_files).transpose()?;
+    let overlaps = dirs::overlaps(&groups, inventory.as_deref());
+
+    if let Some(path) = &args.output {
+        output::write_json_atomic(path, &overlaps)?;
+    }
+
+    if verbosity.quiet() {
+        return Ok(());
+    }
+
+    let limit = if args.limit == 0 { usize::MAX } else { args.limit };
+    for (i, overlap) in overlaps.iter().take(limit).enumerate() {
+        print!("#{}  ", i + 1);
+        print_overlap(overlap);
+        println!();
+    }
+
+    println!("=== Statistics ===");
+    println!("Folder pairs          : {}", overlaps.len());
+    if let Some(path) = &args.output {
+        println!("\nOutput written to:\n  {}", path.display());
+    }
+    Ok(())
+}
+
+/// Shared groups and bytes of a folder pair, then each folder numbered from 1
+/// with the share of it that is duplicated in the other.
+pub fn print_overlap(overlap: &FolderOverlap) {
+    println!(
+        "{} shared group(s), {:.2} MB",
+        overlap.groups,
+        overlap.bytes as f64 / 1_048_576.0
+    );
+    for (i, folder) in overlap.folders.iter().enumerate() {
+        println!(
+            "  [{}] {}  {:.0}% of {:.2} MB also in [{}]",
+            i + 1,
+            folder.dir,
+            folder.percent,
+            folder.bytes as f64 / 1_048_576.0,
+            2 - i
+        );
+    }
+}
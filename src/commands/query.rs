@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use regex::Regex;
+use std::path::PathBuf;
+
+use dedup::query::{self, Filter, Record, SortKey};
+use dedup::records;
+use dedup::report::format_size;
+use dedup::store::{self, Store};
+use dedup::types::FileEntry;
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct QueryArgs {
+    /// duplicates or all_files (JSON, NDJSON) or a scan database
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Write the matching records to this file instead of printing a table,
+    /// NDJSON for .ndjson/.jsonl
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Query the files of a scan database instead of its duplicate groups
+    #[arg(long)]
+    files: bool,
+
+    /// Sort key [default: wasted for groups, size for files]
+    #[arg(short, long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Reverse the sort order
+    #[arg(short, long)]
+    reverse: bool,
+
+    /// Only paths starting with this prefix
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Only paths matching this regular expression
+    #[arg(long)]
+    regex: Option<String>,
+
+    /// Only files of at least this many bytes
+    #[arg(long)]
+    min_size: Option<u64>,
+
+    /// Only these extensions, e.g. jpg,heic
+    #[arg(long, value_delimiter = ',')]
+    ext: Vec<String>,
+
+    /// Only groups with at least this many copies
+    #[arg(long)]
+    min_count: Option<usize>,
+
+    /// Only groups with at most this many copies
+    #[arg(long)]
+    max_count: Option<usize>,
+
+    /// Stop after this many results
+    #[arg(short = 'l', long)]
+    limit: Option<usize>,
+}
+
+pub fn run(args: QueryArgs, verbosity: Verbosity) -> Result<()> {
+    let filter = Filter {
+        prefix: args.prefix.clone(),
+        regex: args
+            .regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid --regex")?,
+        min_size: args.min_size,
+        extensions: args
+            .ext
+            .iter()
+            .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+            .collect(),
+        min_count: args.min_count,
+        max_count: args.max_count,
+    };
+
+    // ---- read and filter, keeping only the matches in memory
+    let mut groups = Vec::new();
+    let mut files = Vec::new();
+    let mut total = 0u64;
+    // shape of the first record, `true` for files, checked before filtering
+    let mut shape: Option<bool> = None;
+    if store::is_database(&args.input) {
+        let store = Store::open_existing(&args.input)?;
+        if args.files {
+            store.for_each_file(|file| {
+                total += 1;
+                if filter.matches_file(&file) {
+                    files.push(file);
+                }
+                Ok(())
+            })?;
+        } else {
+            store.for_each_group(|group| {
+                total += 1;
+                if filter.matches_group(&group) {
+                    groups.push(group);
+                }
+                Ok(())
+            })?;
+        }
+    } else {
+        records::for_each(&args.input, |record: Record| {
+            total += 1;
+            let is_file = matches!(record, Record::File(_));
+            if shape.is_some_and(|first| first != is_file) {
+                bail!("{} mixes groups and files", args.input.display());
+            }
+            shape = Some(is_file);
+            match record {
+                Record::Group(group) => {
+                    if filter.matches_group(&group) {
+                        groups.push(group);
+                    }
+                }
+                Record::File(file) => {
+                    if filter.matches_file(&file) {
+                        files.push(*file);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    // an empty input can't tell the shape and is treated as groups
+    let flat = shape.unwrap_or(false) || (args.files && store::is_database(&args.input));
+    if flat {
+        if filter.needs_groups() {
+            bail!("--min-count and --max-count only apply to duplicate groups");
+        }
+        if args.sort.is_some_and(SortKey::needs_groups) {
+            bail!("sorting by wasted bytes or copies only applies to duplicate groups");
+        }
+    }
+
+    // ---- sort and limit
+    let key = args.sort.unwrap_or(if flat { SortKey::Size } else { SortKey::Wasted });
+    query::sort_files(&mut files, key, args.reverse);
+    query::sort_groups(&mut groups, key, args.reverse);
+    if let Some(limit) = args.limit {
+        files.truncate(limit);
+        groups.truncate(limit);
+    }
+
+    // ---- output
+    if let Some(output) = &args.output {
+        let meta = if store::is_database(&args.input) {
+            None
+        } else {
+            records::read_meta(&args.input)?
+        };
+        if flat {
+            records::write_all(output, meta.as_ref(), &files)?;
+        } else {
+            records::write_all(output, meta.as_ref(), &groups)?;
+        }
+        if !verbosity.quiet() {
+            println!(
+                "{} of {} record(s) matched, {} written.",
+                files.len() + groups.len(),
+                total,
+                output.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if flat {
+        print_files(&files);
+    } else {
+        print_groups(&groups);
+    }
+    if !verbosity.quiet() {
+        println!("\n{} of {} record(s) shown.", files.len() + groups.len(), total);
+    }
+    Ok(())
+}
+
+fn print_groups(groups: &[Vec<FileEntry>]) {
+    println!("{:>12} {:>12} {:>6}  path", "wasted", "size", "copies");
+    for group in groups {
+        let Some(first) = group.first() else {
+            continue;
+        };
+        println!(
+            "{:>12} {:>12} {:>6}  {}",
+            format_size(query::wasted(group)),
+            format_size(first.size),
+            group.len(),
+            first.path
+        );
+        for other in &group[1..] {
+            println!("{:>32}  {}", "", other.path);
+        }
+    }
+}
+
+fn print_files(files: &[FileEntry]) {
+    println!("{:>12} {:>16}  path", "size", "modified");
+    for file in files {
+        let mtime = file
+            .mtime
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!("{:>12} {:>16}  {}", format_size(file.size), mtime, file.path);
+    }
+}
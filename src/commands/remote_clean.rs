@@ -0,0 +1,530 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use reqwest::StatusCode;
+
+use dedup::archive;
+use dedup::audit;
+use dedup::client::ClientOptions;
+use dedup::meta;
+use dedup::quality::{self, Criterion, DEFAULT_CRITERIA};
+use dedup::resume::{DeleteState, ItemStatus};
+use dedup::similar::{self, DEFAULT_MAX_DISTANCE};
+use dedup::types::FileEntry;
+use dedup::video::DEFAULT_MIN_SIMILARITY;
+use dedup::webdav;
+
+use super::{CredentialArgs, InputCheckArgs, Verbosity};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+const DEFAULT_BASE_URL: &str = "https://nrwv2yxngcbjcw6n.myfritz.net/remote.php/dav/files/trwa";
+const DEFAULT_CAMERA_PREFIX: &str =
+    "/var/lib/docker/volumes/nextcloud_aio_nextcloud_data/_data/trwa/files/SofortUpload/Camera/";
+
+/// Which files of a group survive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Policy {
+    /// Keep camera uploads filed as <prefix>YYYY/MM/YYYYMM…, delete the other copies
+    Camera,
+    /// Keep files below --prefix, delete the other copies
+    KeepPrefix,
+    /// Delete files below --prefix if a copy exists elsewhere
+    DropPrefix,
+    /// Keep the best copy by --prefer, e.g. the camera original of a similar group
+    BestQuality,
+}
+
+#[derive(Args)]
+pub struct RemoteCleanArgs {
+    /// Duplicate or similar groups with paths below the Nextcloud data
+    /// directory or URLs below --base-url, as written by a WebDAV scan
+    #[arg(short, long, default_value = "duplicates.json")]
+    input: PathBuf,
+
+    #[arg(long, value_enum)]
+    policy: Policy,
+
+    /// Path prefix for the policy [default for camera: the SofortUpload/Camera folder]
+    #[arg(long, required_if_eq_any = [("policy", "keep-prefix"), ("policy", "drop-prefix")])]
+    prefix: Option<String>,
+
+    /// Criteria of the best-quality policy, most important first; later ones
+    /// break ties [default: resolution,camera,metadata,original-path,size]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    prefer: Vec<Criterion>,
+
+    /// Best-quality policy: only delete images whose perceptual hash differs
+    /// from the kept one in at most this many bits
+    #[arg(long, default_value_t = DEFAULT_MAX_DISTANCE)]
+    max_distance: u32,
+
+    /// Best-quality policy: only delete videos at least this similar to the
+    /// kept one
+    #[arg(long, default_value_t = DEFAULT_MIN_SIMILARITY)]
+    min_similarity: f64,
+
+    /// WebDAV files URL of the user, `…/remote.php/dav/files/<user>`
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    base_url: String,
+
+    /// Only show what would be deleted (the default)
+    #[arg(short = 'n', long, overrides_with = "no_dry_run")]
+    dry_run: bool,
+
+    /// Really delete files
+    #[arg(long, overrides_with = "dry_run")]
+    no_dry_run: bool,
+
+    /// Source label of the server's scan in merged inventories [default: host
+    /// name]; files of other sources are never deleted
+    #[arg(long)]
+    source: Option<String>,
+
+    /// Skip URLs an earlier run already deleted
+    #[arg(long)]
+    resume: bool,
+
+    /// List the deleted items still sitting in the Nextcloud trashbin
+    #[arg(long)]
+    trashbin: bool,
+
+    /// Permanently remove the deleted items from the trashbin
+    #[arg(long)]
+    purge_trashbin: bool,
+
+    #[command(flatten)]
+    input_check: InputCheckArgs,
+
+    #[command(flatten)]
+    credentials: CredentialArgs,
+
+    #[command(flatten)]
+    client: ClientOptions,
+}
+
+pub fn run(args: RemoteCleanArgs, verbosity: Verbosity) -> Result<()> {
+    let json_file = &args.input;
+    let dry_run = !args.no_dry_run;
+    let resume = args.resume;
+    let show_trashbin = args.trashbin;
+    let purge_trashbin = args.purge_trashbin;
+    let base_url = args.base_url.trim_end_matches('/');
+
+    args.input_check.verify(json_file)?;
+
+    let policy = match args.policy {
+        Policy::Camera => KeepPolicy::Camera(
+            args.prefix.clone().unwrap_or_else(|| DEFAULT_CAMERA_PREFIX.to_string()),
+        ),
+        Policy::KeepPrefix => KeepPolicy::KeepPrefix(args.prefix.clone().unwrap_or_default()),
+        Policy::DropPrefix => KeepPolicy::DropPrefix(args.prefix.clone().unwrap_or_default()),
+        Policy::BestQuality => KeepPolicy::BestQuality {
+            criteria: if args.prefer.is_empty() { DEFAULT_CRITERIA.to_vec() } else { args.prefer.clone() },
+            max_distance: args.max_distance,
+            min_similarity: args.min_similarity,
+        },
+    };
+
+    // local paths look like …/<user>/files/<rel-path>, those of a WebDAV scan
+    // like <base-url>/<rel-path>
+    let user_dir = base_url
+        .rsplit('/')
+        .next()
+        .filter(|u| !u.is_empty())
+        .context("--base-url must end with the user name")?;
+    let files_marker = format!("/{}/files/", user_dir);
+    // scans store URLs with a decoded path
+    let url_prefix = format!(
+        "{}/",
+        urlencoding::decode(base_url).map_or_else(|_| base_url.to_string(), |s| s.into_owned())
+    );
+
+    if dry_run {
+        println!("{YELLOW}⚠️  DRY-RUN mode enabled – no files will be deleted{RESET}");
+    } else {
+        println!("{RED}🔥 LIVE MODE – files will be permanently deleted!{RESET}");
+    }
+
+    // ─────────────────────────────────────────────
+    // Safety confirmation
+    // ─────────────────────────────────────────────
+    if !dry_run {
+        print!("{RED}Are you sure? Type 'DELETE' to continue:{RESET} ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim() != "DELETE" {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let client = args.client.build()?;
+
+    // credentials are only needed when we actually talk to the server
+    let credentials = if dry_run {
+        None
+    } else {
+        Some(args.credentials.resolve(base_url)?)
+    };
+
+    // ─────────────────────────────────────────────
+    // Collect delete URLs according to policy, streaming the groups
+    // ─────────────────────────────────────────────
+    let mut delete_urls = Vec::new();
+    let local_source = args.source.clone().unwrap_or_else(meta::local_hostname);
+    let mut foreign = 0usize;
+    let mut unmatched = 0usize;
+
+    super::for_each_group(json_file, |mut group: Vec<FileEntry>| {
+        // paths of other sources belong to other servers
+        let before = group.len();
+        group.retain(|f| !audit::is_foreign(f, Some(&local_source)));
+        foreign += before - group.len();
+
+        // similar groups are transitive, not copies of one file
+        if !matches!(policy, KeepPolicy::BestQuality { .. }) && similar::is_similar_group(&group) {
+            bail!(
+                "{} holds similar groups, which are not byte-identical; only --policy best-quality cleans them",
+                json_file.display()
+            );
+        }
+        if let Some(indices_to_delete) = policy.files_to_delete(&group) {
+            for idx in indices_to_delete {
+                let file = &group[idx];
+                if archive::is_member(&file.path) {
+                    continue; // read-only, the archive may hold other files
+                }
+
+                let rel_path = file
+                    .path
+                    .strip_prefix(url_prefix.as_str())
+                    .or_else(|| file.path.find(&files_marker).map(|pos| &file.path[pos + files_marker.len()..]));
+                if let Some(rel_path) = rel_path {
+                    let encoded_path = rel_path
+                        .split('/')
+                        .map(|s| urlencoding::encode(s))
+                        .collect::<Vec<_>>()
+                        .join("/");
+
+                    let url = format!("{}/{}", base_url, encoded_path);
+                    delete_urls.push(url);
+                } else {
+                    unmatched += 1;
+                }
+            }
+        } else {
+            // Optionally log skipped groups
+            // println!("Skipping group: no policy decision made");
+        }
+        Ok(())
+    })?;
+
+    let total = delete_urls.len();
+    if unmatched > 0 {
+        println!(
+            "{YELLOW}⚠️  Skipping {} file(s) neither below {} nor a …{}… path{RESET}",
+            unmatched, base_url, files_marker
+        );
+    }
+    if foreign > 0 {
+        println!("{YELLOW}ℹ️  Ignoring {} file(s) of other sources than {}{RESET}", foreign, local_source);
+    }
+    println!("🗑️  Files scheduled for deletion: {}", total);
+
+    // ─────────────────────────────────────────────
+    // Delete with progress indicator
+    // ─────────────────────────────────────────────
+    let mut ok_count = 0;
+    let mut error_count = 0;
+    let mut skipped_count = 0;
+
+    let mut state = DeleteState::load(json_file)?;
+    if !dry_run {
+        let deleted = state.count(ItemStatus::Deleted);
+        let failed = state.count(ItemStatus::Failed);
+        if resume {
+            println!(
+                "⏩ Resuming from {}: {} deleted, {} failed before",
+                state.path().display(),
+                deleted,
+                failed
+            );
+        } else if deleted + failed > 0 {
+            println!(
+                "{YELLOW}ℹ️  {} has {} deleted and {} failed item(s), use --resume to skip finished ones{RESET}",
+                state.path().display(),
+                deleted,
+                failed
+            );
+        }
+    }
+
+    for (idx, url) in delete_urls.iter().enumerate() {
+        let current = idx + 1;
+        let percent = (current as f64 / total as f64) * 100.0;
+
+        if !verbosity.quiet() {
+            print!("\r[{percent:5.1}%] {current} of {total}");
+            io::stdout().flush()?;
+        }
+
+        if dry_run {
+            println!("\nDRY-RUN: would delete {}", url);
+            ok_count += 1;
+            continue;
+        }
+
+        let already_deleted = state.status(url) == Some(ItemStatus::Deleted);
+        if resume && already_deleted {
+            skipped_count += 1;
+            continue;
+        }
+
+        let creds = credentials
+            .as_ref()
+            .expect("credentials are resolved in live mode");
+
+        match client
+            .delete(url)
+            .basic_auth(&creds.user, Some(&creds.password))
+            .send()
+        {
+            Ok(resp) if resp.status().is_success() => {
+                ok_count += 1;
+                if verbosity.verbose() {
+                    println!("\nDeleted {}", url);
+                }
+                state.record(url, ItemStatus::Deleted)?;
+            }
+            // gone already because an earlier run deleted it
+            Ok(resp) if resp.status() == StatusCode::NOT_FOUND && already_deleted => {
+                ok_count += 1;
+            }
+            Ok(resp) => {
+                error_count += 1;
+                state.record(url, ItemStatus::Failed)?;
+                eprintln!(
+                    "\n{RED}❌ HTTP {} for {}{RESET}",
+                    resp.status(),
+                    url
+                );
+            }
+            Err(e) => {
+                error_count += 1;
+                state.record(url, ItemStatus::Failed)?;
+                eprintln!(
+                    "\n{RED}❌ Request error for {}: {}{RESET}",
+                    url, e
+                );
+            }
+        }
+    }
+
+    // ─────────────────────────────────────────────
+    // Trashbin: Nextcloud only moves deleted files there
+    // ─────────────────────────────────────────────
+    let mut trash_pending: Option<(usize, u64)> = None;
+    let mut trash_purged: Option<(usize, u64)> = None;
+
+    if !dry_run && (show_trashbin || purge_trashbin) && error_count > 0 {
+        println!(
+            "\n{YELLOW}⚠️  Skipping the trashbin: {} deletion(s) failed, check them and run again{RESET}",
+            error_count
+        );
+    } else if !dry_run && (show_trashbin || purge_trashbin) {
+        let creds = credentials
+            .as_ref()
+            .expect("credentials are resolved in live mode");
+
+        let locations: HashSet<String> = delete_urls
+            .iter()
+            .filter(|url| state.status(url) == Some(ItemStatus::Deleted))
+            .filter_map(|url| webdav::relative_location(base_url, url))
+            .collect();
+
+        let trash_url = webdav::trashbin_url(base_url)?;
+        match webdav::list_trashbin(&client, &trash_url, creds) {
+            Ok(items) => {
+                let matched = webdav::matching_trash_items(&items, &locations);
+                let mut pending = (matched.len(), matched.iter().map(|i| i.size).sum::<u64>());
+
+                if purge_trashbin {
+                    let mut purged = (0usize, 0u64);
+                    for item in &matched {
+                        match webdav::purge_trash_item(&client, item, creds) {
+                            Ok(()) => {
+                                purged.0 += 1;
+                                purged.1 += item.size;
+                            }
+                            Err(e) => {
+                                error_count += 1;
+                                eprintln!(
+                                    "{RED}❌ Purge failed for {}: {}{RESET}",
+                                    item.original_location, e
+                                );
+                            }
+                        }
+                    }
+                    pending.0 -= purged.0;
+                    pending.1 -= purged.1;
+                    trash_purged = Some(purged);
+                } else if !matched.is_empty() {
+                    println!("\n\n♻️  Deleted items in the trashbin:");
+                    for item in &matched {
+                        println!("  {} ({} bytes)", item.original_location, item.size);
+                    }
+                }
+                trash_pending = Some(pending);
+            }
+            Err(e) => {
+                error_count += 1;
+                eprintln!("{RED}❌ Failed to list trashbin {}: {}{RESET}", trash_url, e);
+            }
+        }
+    }
+
+    // ─────────────────────────────────────────────
+    // Summary
+    // ─────────────────────────────────────────────
+    println!("\n\n──────── Summary ────────");
+    println!("{GREEN}✔ Successful:{RESET} {}", ok_count);
+    if skipped_count > 0 {
+        println!("{YELLOW}⏩ Already deleted:{RESET} {}", skipped_count);
+    }
+    println!("{RED}✖ Errors:{RESET} {}", error_count);
+    if let Some((count, bytes)) = trash_purged {
+        println!(
+            "{GREEN}♻️  Purged from trashbin:{RESET} {} ({:.2} MB reclaimed)",
+            count,
+            bytes as f64 / 1_048_576.0
+        );
+    }
+    if let Some((count, bytes)) = trash_pending {
+        println!(
+            "{YELLOW}🗑️  Still in trashbin:{RESET} {} ({:.2} MB, still counted against quota)",
+            count,
+            bytes as f64 / 1_048_576.0
+        );
+    }
+    println!("────────────────────────");
+
+    if error_count > 0 {
+        std::process::exit(2);
+    }
+
+    Ok(())
+}
+
+//
+// ────────────────────────────────────────────────
+// Deletion policy (all decision logic lives here)
+// ────────────────────────────────────────────────
+//
+
+enum KeepPolicy {
+    Camera(String),
+    KeepPrefix(String),
+    DropPrefix(String),
+    /// Ranked by image properties, see [`quality::best_members`]. Members of
+    /// similar groups are only deleted if they are close to the kept one:
+    /// clusters are transitive and may chain unrelated images together.
+    BestQuality {
+        criteria: Vec<Criterion>,
+        max_distance: u32,
+        min_similarity: f64,
+    },
+}
+
+impl KeepPolicy {
+    /// Decide which files in a group should be deleted.
+    ///
+    /// Returns:
+    /// - `Some(indices)` → indices of files in `group` that should be deleted
+    /// - `None` → we have no idea for this group, skip it entirely
+    fn files_to_delete(&self, group: &[FileEntry]) -> Option<Vec<usize>> {
+        if let KeepPolicy::BestQuality { criteria, max_distance, min_similarity } = self {
+            let best = quality::best_members(group, criteria);
+            if best.is_empty() || best.len() == group.len() {
+                return None; // all equally good
+            }
+            let kept = &group[best[0]];
+            let close = |file: &FileEntry| {
+                (file.size == kept.size && file.checksum == kept.checksum)
+                    || matches!((&file.phash, &kept.phash), (Some(a), Some(b))
+                        if similar::is_close(a, b, *max_distance, *min_similarity))
+            };
+            return Some(
+                (0..group.len())
+                    .filter(|idx| !best.contains(idx) && close(&group[*idx]))
+                    .collect(),
+            );
+        }
+
+        let mut has_preferred_entry = false;
+
+        for file in group {
+            if self.is_preferred_entry(file) {
+                has_preferred_entry = true;
+                break;
+            }
+        }
+
+        if !has_preferred_entry {
+            return None; // explicit: we don't know what to delete
+        }
+
+        Some(
+            group
+                .iter()
+                .enumerate()
+                .filter(|(_, file)| !self.is_preferred_entry(file))
+                .map(|(idx, _)| idx)
+                .collect(),
+        )
+    }
+
+    fn is_preferred_entry(&self, file: &FileEntry) -> bool {
+        match self {
+            KeepPolicy::Camera(prefix) => is_valid_sofort_upload(file, prefix),
+            KeepPolicy::KeepPrefix(prefix) => file.path.starts_with(prefix.as_str()),
+            KeepPolicy::DropPrefix(prefix) => !file.path.starts_with(prefix.as_str()),
+            KeepPolicy::BestQuality { .. } => false, // decided per group
+        }
+    }
+}
+
+/// Check whether a file is a valid SofortUpload/Camera file: filed under
+/// `YYYY/MM` of its capture date if the scan read it (`scan --media`), else
+/// of the date its file name starts with.
+fn is_valid_sofort_upload(file: &FileEntry, prefix: &str) -> bool {
+    let rest = match file.path.strip_prefix(prefix) {
+        Some(r) => r,
+        None => return false,
+    };
+
+    let parts: Vec<&str> = rest.split('/').collect();
+    if parts.len() < 3 {
+        return false;
+    }
+
+    let year = parts[0];
+    let month = parts[1];
+    let filename = parts[2];
+
+    if year.len() != 4 || month.len() != 2 {
+        return false;
+    }
+
+    match file.media.as_ref().and_then(|m| m.captured_at) {
+        Some(captured) => captured.format("%Y/%m").to_string() == format!("{}/{}", year, month),
+        None => filename.starts_with(year) && filename.get(4..6) == Some(month),
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::report::{self, ReportFormat};
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Duplicate groups (JSON, NDJSON or scan database)
+    #[arg(short, long, default_value = "duplicates.json")]
+    input: PathBuf,
+
+    /// Report file, .csv or .html; repeat for several reports
+    #[arg(short, long, required = true)]
+    output: Vec<PathBuf>,
+
+    /// Do not embed image thumbnails into HTML reports
+    #[arg(long)]
+    no_thumbnails: bool,
+}
+
+pub fn run(args: ReportArgs, verbosity: Verbosity) -> Result<()> {
+    // check all names before doing any work
+    let formats = args
+        .output
+        .iter()
+        .map(|path| ReportFormat::from_path(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let groups = super::read_groups(&args.input)?;
+
+    for (path, format) in args.output.iter().zip(formats) {
+        match format {
+            ReportFormat::Csv => report::write_csv(path, &groups)?,
+            ReportFormat::Html => report::write_html(path, &groups, !args.no_thumbnails)?,
+        }
+        if !verbosity.quiet() {
+            println!("{} written.", path.display());
+        }
+    }
+    Ok(())
+}
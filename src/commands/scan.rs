@@ -0,0 +1,313 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use std::path::PathBuf;
+use tokio::task;
+
+use dedup::client::ClientOptions;
+use dedup::group::{self, GroupStats, DEFAULT_MEMORY_BUDGET};
+use dedup::hash::HashAlgorithm;
+use dedup::meta::{self, ScanMeta, ScanSettings};
+use dedup::output::OutputLocation;
+use dedup::records::{self, Format, RecordWriter};
+use dedup::scan::{self, Progress, ScanOptions, DEFAULT_MIN_SIZE};
+use dedup::similar::{self, HeicConverter, PerceptualHash, DEFAULT_MAX_DISTANCE};
+use dedup::store::Store;
+use dedup::types::FileEntry;
+use dedup::video::{self, Ffmpeg, DEFAULT_MIN_SIMILARITY};
+use dedup::webdav::{self, RemoteHashing, RemoteScanOptions};
+
+use super::{CredentialArgs, Verbosity};
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// Directory or WebDAV URL to scan
+    root: String,
+
+    /// Directory for the result files
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Prefix for the result file names, e.g. `nas-` gives nas-duplicates.json
+    #[arg(long, default_value = "")]
+    prefix: String,
+
+    /// Write into a new <output-dir>/<YYYYmmdd-HHMMSS> directory instead of overwriting
+    #[arg(long)]
+    timestamped: bool,
+
+    /// Format of the result files; ndjson streams one record per line and
+    /// groups within --memory-budget
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Write into this SQLite database instead of JSON files; rescans of the
+    /// same root update it in place
+    #[arg(long, value_name = "FILE", conflicts_with = "format")]
+    db: Option<PathBuf>,
+
+    /// Memory for grouping ndjson results, in MB
+    #[arg(long, default_value_t = DEFAULT_MEMORY_BUDGET / 1_048_576)]
+    memory_budget: u64,
+
+    /// Ignore files smaller than this many bytes
+    #[arg(long, default_value_t = DEFAULT_MIN_SIZE)]
+    min_size: u64,
+
+    /// Hash algorithms, comma separated; the first one gives the checksum used
+    /// for grouping, the others are stored alongside, e.g. blake3,sha1,md5
+    #[arg(long = "hash", value_enum, value_delimiter = ',', default_value = "blake3")]
+    hashes: Vec<HashAlgorithm>,
+
+    /// Read capture date, camera, dimensions and duration of images and
+    /// videos into the inventory; local scans only
+    #[arg(long)]
+    media: bool,
+
+    /// Also hash the files inside zip, tar and tar.gz archives, as virtual
+    /// files like photos.zip!/IMG_001.jpg; local scans only
+    #[arg(long)]
+    archives: bool,
+
+    /// Also find visually similar images (re-encoded, resized or stripped
+    /// copies) and write them to similar.json; local scans only
+    #[arg(long, conflicts_with = "db")]
+    similar: bool,
+
+    /// Perceptual hash for --similar
+    #[arg(long, value_enum, default_value_t = PerceptualHash::DHash)]
+    perceptual_hash: PerceptualHash,
+
+    /// Number of bits two perceptual hashes may differ in to count as similar
+    #[arg(long, default_value_t = DEFAULT_MAX_DISTANCE)]
+    max_distance: u32,
+
+    /// HEIC to JPEG converter for --similar, called as `<PATH> <input> <output>`,
+    /// e.g. heif-convert; without it HEIC images are reported and skipped
+    #[arg(long, value_name = "PATH")]
+    heic_converter: Option<PathBuf>,
+
+    /// Also find re-encoded copies of videos by sampling frames with ffmpeg,
+    /// written to similar.json as well; local scans only
+    #[arg(long, conflicts_with = "db")]
+    similar_videos: bool,
+
+    /// ffmpeg binary for --similar-videos
+    #[arg(long, value_name = "PATH", default_value = "ffmpeg")]
+    ffmpeg: PathBuf,
+
+    /// Similarity from 0 to 1 from which two videos count as the same clip
+    #[arg(long, default_value_t = DEFAULT_MIN_SIMILARITY)]
+    min_similarity: f64,
+
+    /// Download and hash remote files without a server checksum, with the
+    /// algorithm of the server checksums so both group together
+    #[arg(long, conflicts_with = "download_all")]
+    download: bool,
+
+    /// Ignore server checksums and download every remote file
+    #[arg(long)]
+    download_all: bool,
+
+    #[command(flatten)]
+    credentials: CredentialArgs,
+
+    #[command(flatten)]
+    client: ClientOptions,
+}
+
+pub async fn run(args: ScanArgs, verbosity: Verbosity) -> Result<()> {
+    let started_at = meta::now();
+    let root = args.root;
+    let hashing = if args.download_all {
+        RemoteHashing::DownloadAll
+    } else if args.download {
+        RemoteHashing::DownloadMissing
+    } else {
+        RemoteHashing::ServerOnly
+    };
+
+    let mut location = OutputLocation::new(&args.output_dir, args.prefix.as_str());
+    if args.timestamped {
+        location = location.timestamped();
+    }
+    location.create_dir()?;
+    let ext = args.format.extension();
+    let all_files_path = location.path(&format!("all_files.{}", ext));
+    let duplicates_path = location.path(&format!("duplicates.{}", ext));
+    let similar_path = location.path(&format!("similar.{}", ext));
+
+    if !verbosity.quiet() {
+        println!("Processing folder: {}\n", root);
+    }
+
+    let scan_root = root.clone();
+    let remote = root.starts_with("http://") || root.starts_with("https://");
+    if remote && (args.similar || args.similar_videos || args.media || args.archives) {
+        bail!("--similar, --similar-videos, --media and --archives only work for local scans");
+    }
+    // fail before hashing everything if ffmpeg is missing
+    let ffmpeg = args.similar_videos.then(|| Ffmpeg::new(&args.ffmpeg)).transpose()?;
+    let heic = args.heic_converter.as_ref().map(HeicConverter::new).transpose()?;
+    let (mut files, total_files, total_bytes) =
+        if remote {
+            let options = RemoteScanOptions {
+                min_size: args.min_size,
+                hashing,
+                algorithms: args.hashes.clone(),
+            };
+            // reqwest's blocking client must not run on the async runtime
+            let mut files = task::spawn_blocking(move || -> Result<Vec<FileEntry>> {
+                let creds = args.credentials.resolve(&root)?;
+                let client = args.client.build()?;
+                let (mut count, mut bytes) = (0, 0);
+                webdav::scan(&client, &root, &creds, &options, |entry| {
+                    count += 1;
+                    bytes += entry.size;
+                    print_progress(
+                        verbosity,
+                        &Progress {
+                            path: &entry.path,
+                            size: entry.size,
+                            files: count,
+                            bytes,
+                        },
+                    )
+                })
+            })
+            .await??;
+
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            let total_files = files.len() as u64;
+            let total_bytes = files.iter().map(|f| f.size).sum();
+            (files, total_files, total_bytes)
+        } else {
+            let options = ScanOptions::builder(&root)
+                .min_size(args.min_size)
+                .hashes(&args.hashes)
+                .media(args.media)
+                .archives(args.archives)
+                .on_progress(move |p| print_progress(verbosity, p))
+                .build();
+
+            let result = scan::scan(&options).await?;
+            for error in &result.errors {
+                eprintln!("Error processing file {}: {}", error.path.display(), error.message);
+            }
+            (result.files, result.total_files, result.total_bytes)
+        };
+
+    if args.similar {
+        for error in similar::hash_images(&mut files, args.perceptual_hash, heic.as_ref()).await? {
+            eprintln!("Error hashing image {}: {}", error.path.display(), error.message);
+        }
+    }
+    if let Some(ffmpeg) = &ffmpeg {
+        for error in video::fingerprint_videos(&mut files, ffmpeg).await? {
+            eprintln!("Error fingerprinting video {}: {}", error.path.display(), error.message);
+        }
+    }
+
+    if let Some(db_path) = &args.db {
+        let mut store = Store::open(db_path)?;
+        let run_id = store.begin_run(&scan_root)?;
+        store.upsert_files(run_id, &files)?;
+        let stats = store.finish_run(run_id, &scan_root)?;
+
+        print_statistics(total_files, total_bytes, &stats);
+        println!("\nOutput written to:");
+        println!("  {} (run {})", db_path.display(), run_id);
+        return Ok(());
+    }
+
+    let settings = ScanSettings {
+        min_size: args.min_size,
+        follow_links: false,
+        remote_hashing: remote.then(|| hashing.name().to_string()),
+        hashes: args.hashes.iter().map(|a| a.name().to_string()).collect(),
+        archives: args.archives,
+    };
+    let primary = args.hashes.first().copied().unwrap_or(HashAlgorithm::Blake3);
+    let hash_algorithm = if remote {
+        hashing.hash_algorithm(primary)
+    } else {
+        primary.name().to_string()
+    };
+    let meta = ScanMeta::new(vec![scan_root], settings, &hash_algorithm, started_at);
+
+    records::write_all(&all_files_path, Some(&meta), &files)?;
+
+    // ---- cluster similar images and videos before the inventory may be dropped below
+    let similar_stats = if args.similar || args.similar_videos {
+        let mut clusters = similar::cluster(&files, args.max_distance);
+        clusters.extend(video::cluster(&files, args.min_similarity));
+        records::write_all(&similar_path, Some(&meta), &clusters)?;
+        Some((clusters.iter().map(Vec::len).sum::<usize>(), clusters.len()))
+    } else {
+        None
+    };
+
+    // ---- find duplicates
+    let stats = match args.format {
+        Format::Json => {
+            let duplicates = group::group(&files);
+            records::write_all(&duplicates_path, Some(&meta), &duplicates)?;
+            GroupStats::from_groups(&duplicates)
+        }
+        Format::Ndjson => {
+            // group from the written inventory instead of keeping it in memory
+            drop(files);
+            let mut writer = RecordWriter::create(&duplicates_path, Some(&meta))?;
+            let stats = group::group_file(
+                &all_files_path,
+                args.memory_budget.saturating_mul(1_048_576),
+                |group| writer.write(&group),
+            )?;
+            writer.finish()?;
+            stats
+        }
+    };
+
+    print_statistics(total_files, total_bytes, &stats);
+    if let Some((similar_files, groups)) = similar_stats {
+        println!("Similar files         : {}", similar_files);
+        println!("Similar groups        : {}", groups);
+    }
+
+    println!("\nOutput written to:");
+    println!("  {}", all_files_path.display());
+    println!("  {}", duplicates_path.display());
+    if similar_stats.is_some() {
+        println!("  {}", similar_path.display());
+    }
+
+    Ok(())
+}
+
+fn print_statistics(total_files: u64, total_bytes: u64, stats: &GroupStats) {
+    println!("\n=== Statistics ===");
+    println!("Total files           : {}", total_files);
+    println!(
+        "Total data processed  : {:.2} MB",
+        total_bytes as f64 / 1_048_576.0
+    );
+    println!("Duplicate files       : {}", stats.duplicate_files);
+    println!("Duplicate groups      : {}", stats.duplicate_groups);
+    println!(
+        "Potential savings     : {:.2} MB",
+        stats.potential_savings as f64 / 1_048_576.0
+    );
+}
+
+fn print_progress(verbosity: Verbosity, progress: &Progress) {
+    if verbosity.verbose() {
+        println!("{}", progress.path);
+    }
+
+    if progress.files.is_multiple_of(100) && !verbosity.quiet() {
+        println!(
+            "Processed {:>8} files ({:.2} MB)",
+            progress.files,
+            progress.bytes as f64 / 1_048_576.0
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use dedup::records;
+use dedup::similar::{self, HeicConverter, PerceptualHash, DEFAULT_MAX_DISTANCE};
+use dedup::video::{self, Ffmpeg, DEFAULT_MIN_SIMILARITY};
+
+use super::Verbosity;
+
+#[derive(Args)]
+pub struct SimilarArgs {
+    /// Inventory, e.g. all_files.json of `scan --similar` or `--similar-videos`
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Similar groups, .ndjson/.jsonl for NDJSON, JSON otherwise
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Hash local images with this algorithm first; without it only the
+    /// perceptual hashes already in the input are used
+    #[arg(long, value_enum)]
+    perceptual_hash: Option<PerceptualHash>,
+
+    /// Number of bits two perceptual hashes may differ in to count as similar
+    #[arg(long, default_value_t = DEFAULT_MAX_DISTANCE)]
+    max_distance: u32,
+
+    /// HEIC to JPEG converter for --perceptual-hash, called as
+    /// `<PATH> <input> <output>`, e.g. heif-convert
+    #[arg(long, value_name = "PATH", requires = "perceptual_hash")]
+    heic_converter: Option<PathBuf>,
+
+    /// Fingerprint local videos without a fingerprint with ffmpeg first
+    #[arg(long)]
+    videos: bool,
+
+    /// ffmpeg binary for --videos
+    #[arg(long, value_name = "PATH", default_value = "ffmpeg")]
+    ffmpeg: PathBuf,
+
+    /// Similarity from 0 to 1 from which two videos count as the same clip
+    #[arg(long, default_value_t = DEFAULT_MIN_SIMILARITY)]
+    min_similarity: f64,
+}
+
+pub async fn run(args: SimilarArgs, verbosity: Verbosity) -> Result<()> {
+    let ffmpeg = args.videos.then(|| Ffmpeg::new(&args.ffmpeg)).transpose()?;
+    let heic = args.heic_converter.as_ref().map(HeicConverter::new).transpose()?;
+    let meta = records::read_meta(&args.input)?;
+    let mut files = super::read_files(&args.input)?;
+
+    if let Some(kind) = args.perceptual_hash {
+        for error in similar::hash_images(&mut files, kind, heic.as_ref()).await? {
+            eprintln!("Error hashing image {}: {}", error.path.display(), error.message);
+        }
+    }
+
+    if let Some(ffmpeg) = &ffmpeg {
+        for error in video::fingerprint_videos(&mut files, ffmpeg).await? {
+            eprintln!("Error fingerprinting video {}: {}", error.path.display(), error.message);
+        }
+    }
+
+    let mut clusters = similar::cluster(&files, args.max_distance);
+    clusters.extend(video::cluster(&files, args.min_similarity));
+    records::write_all(&args.output, meta.as_ref(), &clusters)?;
+
+    if !verbosity.quiet() {
+        println!(
+            "{} similar group(s) with {} files, {} written.",
+            clusters.len(),
+            clusters.iter().map(Vec::len).sum::<usize>(),
+            args.output.display()
+        );
+    }
+    Ok(())
+}
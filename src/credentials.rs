@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Environment variable holding the WebDAV login name.
+pub const USER_ENV: &str = "DEDUP_WEBDAV_USER";
+/// Environment variable holding the WebDAV app password.
+pub const PASSWORD_ENV: &str = "DEDUP_WEBDAV_PASSWORD";
+
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+}
+
+/// Resolve WebDAV credentials for `host`.
+///
+/// Lookup order, first hit wins (user and password are resolved independently):
+/// 1. command line arguments (a password given there prints a warning)
+/// 2. `DEDUP_WEBDAV_USER` / `DEDUP_WEBDAV_PASSWORD`
+/// 3. credentials file (`--credentials-file` or `~/.config/dedup/credentials`)
+/// 4. `~/.netrc` (or `$NETRC`) entry for `host`
+/// 5. interactive prompt, the password is read without echo
+pub fn resolve(
+    host: &str,
+    user_arg: Option<&str>,
+    password_arg: Option<&str>,
+    credentials_file: Option<&Path>,
+) -> Result<Credentials> {
+    if password_arg.is_some() {
+        eprintln!(
+            "⚠️  Passing the app password as an argument exposes it in shell history and `ps`. \
+             Use {}, ~/.netrc or a credentials file instead.",
+            PASSWORD_ENV
+        );
+    }
+
+    let file = match credentials_file {
+        Some(path) => Some(read_credentials_file(path)?),
+        None => match default_credentials_file() {
+            Some(path) if path.exists() => Some(read_credentials_file(&path)?),
+            _ => None,
+        },
+    };
+
+    let mut user = user_arg
+        .map(str::to_string)
+        .or_else(|| non_empty_env(USER_ENV))
+        .or_else(|| file.as_ref().and_then(|f| f.user.clone()));
+
+    let mut password = password_arg
+        .map(str::to_string)
+        .or_else(|| non_empty_env(PASSWORD_ENV))
+        .or_else(|| file.as_ref().and_then(|f| f.password.clone()));
+
+    if user.is_none() || password.is_none() {
+        if let Some(entry) = netrc_entry(host, user.as_deref())? {
+            if user.is_none() {
+                user = entry.login;
+            }
+            if password.is_none() {
+                password = entry.password;
+            }
+        }
+    }
+
+    let user = match user {
+        Some(u) => u,
+        None => prompt_line(&format!("WebDAV user for {}: ", host))?,
+    };
+
+    let password = match password {
+        Some(p) => p,
+        None => rpassword::prompt_password(format!("App password for {}@{}: ", user, host))
+            .context("Failed to read password")?,
+    };
+
+    Ok(Credentials { user, password })
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+fn default_credentials_file() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|h| h.join(".config")))?;
+    Some(config.join("dedup").join("credentials"))
+}
+
+fn prompt_line(prompt: &str) -> Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim().to_string();
+    if input.is_empty() {
+        bail!("No user given");
+    }
+    Ok(input)
+}
+
+// ─────────────────────────────────────────────
+// Credentials file
+// ─────────────────────────────────────────────
+
+#[derive(Default)]
+struct FileCredentials {
+    user: Option<String>,
+    password: Option<String>,
+}
+
+/// Read a `key = value` credentials file with the keys `user` and `password`.
+///
+/// The file must not be accessible by group or others.
+fn read_credentials_file(path: &Path) -> Result<FileCredentials> {
+    check_permissions(path)?;
+
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut creds = FileCredentials::default();
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("{}:{}: expected `key = value`", path.display(), lineno + 1))?;
+        let value = value.trim().to_string();
+        match key.trim() {
+            "user" => creds.user = Some(value),
+            "password" => creds.password = Some(value),
+            other => bail!("{}:{}: unknown key `{}`", path.display(), lineno + 1, other),
+        }
+    }
+    Ok(creds)
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .with_context(|| format!("Failed to stat {}", path.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "{} is accessible by other users (mode {:o}), run `chmod 600 {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+// netrc
+// ─────────────────────────────────────────────
+
+#[derive(Default)]
+struct NetrcEntry {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+/// Find the netrc entry for `host`, falling back to `default`.
+/// If `login` is known only an entry with that login matches.
+fn netrc_entry(host: &str, login: Option<&str>) -> Result<Option<NetrcEntry>> {
+    let path = match env::var_os("NETRC").map(PathBuf::from) {
+        Some(p) => p,
+        None => match home_dir() {
+            Some(h) => h.join(".netrc"),
+            None => return Ok(None),
+        },
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(parse_netrc(&text, host, login))
+}
+
+/// The entry of netrc `text` for `host`, see [`netrc_entry`].
+fn parse_netrc(text: &str, host: &str, login: Option<&str>) -> Option<NetrcEntry> {
+    let text = strip_macros(text);
+    let mut tokens = text.split_whitespace();
+    let mut entries: Vec<(Option<String>, NetrcEntry)> = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            // a `machine` without a name at the end must not act as `default`
+            "machine" => match tokens.next() {
+                Some(name) => entries.push((Some(name.to_string()), NetrcEntry::default())),
+                None => break,
+            },
+            "default" => entries.push((None, NetrcEntry::default())),
+            "login" | "password" | "account" => {
+                let value = tokens.next().map(str::to_string);
+                if let Some((_, entry)) = entries.last_mut() {
+                    match token {
+                        "login" => entry.login = value,
+                        "password" => entry.password = value,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let matches_login = |e: &NetrcEntry| match login {
+        Some(l) => e.login.as_deref() == Some(l),
+        None => true,
+    };
+
+    let found = entries
+        .iter()
+        .position(|(m, e)| m.as_deref() == Some(host) && matches_login(e))
+        .or_else(|| entries.iter().position(|(m, e)| m.is_none() && matches_login(e)));
+
+    found.map(|i| entries.swap_remove(i).1)
+}
+
+/// `text` without `macdef` definitions: the rest of their line and the macro
+/// body up to the next empty line, whose words are no netrc tokens.
+fn strip_macros(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_macro = false;
+    for line in text.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token == "macdef" {
+                in_macro = true;
+                break;
+            }
+            stripped.push_str(token);
+            stripped.push(' ');
+        }
+        stripped.push('\n');
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netrc_falls_back_to_default() {
+        let text = "machine other.example login bob password b\ndefault login alice password a\n";
+        let entry = parse_netrc(text, "cloud.example", None).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("alice"));
+        assert_eq!(entry.password.as_deref(), Some("a"));
+
+        let entry = parse_netrc(text, "other.example", None).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn netrc_login_must_match() {
+        let text = "machine cloud.example login bob password b\n";
+        assert!(parse_netrc(text, "cloud.example", Some("alice")).is_none());
+        assert!(parse_netrc(text, "cloud.example", Some("bob")).is_some());
+    }
+
+    #[test]
+    fn netrc_skips_macro_bodies() {
+        let text = "machine cloud.example login alice\n\
+                    macdef init\n\
+                    machine cloud.example login mallory password m\n\
+                    \n\
+                    machine cloud.example password a\n";
+        let entry = parse_netrc(text, "cloud.example", None).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("alice"));
+        assert_eq!(entry.password.as_deref(), None);
+
+        // the entry after the macro is parsed again
+        let entry = parse_netrc(text, "cloud.example", Some("alice")).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn netrc_unterminated_entries_are_empty() {
+        let entry = parse_netrc("machine", "cloud.example", None);
+        assert!(entry.is_none());
+        let entry = parse_netrc("default login", "cloud.example", None).unwrap();
+        assert_eq!(entry.login, None);
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::FileEntry;
+
+/// A file whose content changed between two scans.
+#[derive(Serialize, Debug, Clone)]
+pub struct Modified {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub old_checksum: String,
+    pub new_checksum: String,
+}
+
+/// A file that disappeared at one path and showed up with the same content
+/// at another.
+#[derive(Serialize, Debug, Clone)]
+pub struct Moved {
+    pub from: String,
+    pub to: String,
+    pub size: u64,
+    pub checksum: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct DiffSummary {
+    pub added: u64,
+    pub deleted: u64,
+    pub modified: u64,
+    pub moved: u64,
+    /// Files at the same path and of the same size without a hash by a
+    /// common algorithm, so it is unknown whether they changed
+    pub unverified: u64,
+    pub added_bytes: u64,
+    pub deleted_bytes: u64,
+}
+
+/// Changes from an old to a new inventory, each list sorted by path.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Diff {
+    pub summary: DiffSummary,
+    pub added: Vec<FileEntry>,
+    pub deleted: Vec<FileEntry>,
+    pub modified: Vec<Modified>,
+    pub moved: Vec<Moved>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.deleted.is_empty() && self.modified.is_empty() && self.moved.is_empty()
+    }
+}
+
+/// Compare two inventories by path, then pair up deleted and added files
+/// with the same `(size, checksum)` as moves. Files at the same path are
+/// compared by the first algorithm both have a hash by, see [`same_content`].
+pub fn diff(old: &[FileEntry], new: &[FileEntry]) -> Diff {
+    let old_by_path: HashMap<&str, &FileEntry> = old.iter().map(|f| (f.path.as_str(), f)).collect();
+    let new_by_path: HashMap<&str, &FileEntry> = new.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let mut result = Diff::default();
+
+    // ---- same path
+    let mut unverified = 0;
+    for f in new {
+        if let Some(o) = old_by_path.get(f.path.as_str()) {
+            let same = same_content(o, f);
+            if same.is_none() {
+                unverified += 1;
+            }
+            if same == Some(false) {
+                result.modified.push(Modified {
+                    path: f.path.clone(),
+                    old_size: o.size,
+                    new_size: f.size,
+                    old_checksum: o.checksum.clone(),
+                    new_checksum: f.checksum.clone(),
+                });
+            }
+        }
+    }
+
+    // ---- gone and new paths, keyed by content; BTreeMap keeps moves stable
+    let mut gone: BTreeMap<(u64, &str), Vec<&FileEntry>> = BTreeMap::new();
+    for f in old.iter().filter(|f| !new_by_path.contains_key(f.path.as_str())) {
+        gone.entry((f.size, f.checksum.as_str())).or_default().push(f);
+    }
+    let mut appeared: BTreeMap<(u64, &str), Vec<&FileEntry>> = BTreeMap::new();
+    for f in new.iter().filter(|f| !old_by_path.contains_key(f.path.as_str())) {
+        appeared.entry((f.size, f.checksum.as_str())).or_default().push(f);
+    }
+
+    for (key, mut from) in gone {
+        let mut to = appeared.remove(&key).unwrap_or_default();
+        from.sort_by(|a, b| a.path.cmp(&b.path));
+        to.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let pairs = from.len().min(to.len());
+        for (f, t) in from.iter().zip(&to).take(pairs) {
+            result.moved.push(Moved {
+                from: f.path.clone(),
+                to: t.path.clone(),
+                size: f.size,
+                checksum: f.checksum.clone(),
+            });
+        }
+        result.deleted.extend(from[pairs..].iter().map(|f| (*f).clone()));
+        result.added.extend(to[pairs..].iter().map(|f| (*f).clone()));
+    }
+    result.added.extend(appeared.into_values().flatten().cloned());
+
+    result.added.sort_by(|a, b| a.path.cmp(&b.path));
+    result.deleted.sort_by(|a, b| a.path.cmp(&b.path));
+    result.modified.sort_by(|a, b| a.path.cmp(&b.path));
+    result.moved.sort_by(|a, b| a.from.cmp(&b.from));
+
+    result.summary = DiffSummary {
+        added: result.added.len() as u64,
+        deleted: result.deleted.len() as u64,
+        modified: result.modified.len() as u64,
+        moved: result.moved.len() as u64,
+        unverified,
+        added_bytes: result.added.iter().map(|f| f.size).sum(),
+        deleted_bytes: result.deleted.iter().map(|f| f.size).sum(),
+    };
+    result
+}
+
+/// Whether `a` and `b` have the same content: different sizes never do, else
+/// the hashes by the first algorithm both have decide. `None` if there is no
+/// such algorithm, e.g. a BLAKE3 scan against a `--hash sha1` one.
+pub fn same_content(a: &FileEntry, b: &FileEntry) -> Option<bool> {
+    if a.size != b.size {
+        return Some(false);
+    }
+    let primary = a
+        .algorithm
+        .as_deref()
+        .or_else(|| a.checksum.split_once(':').map(|(tag, _)| tag))
+        .unwrap_or("blake3");
+    std::iter::once(primary)
+        .chain(a.hashes.keys().map(String::as_str))
+        .find_map(|algorithm| Some(a.hash(algorithm)? == b.hash(algorithm)?))
+}
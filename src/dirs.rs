@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::types::FileEntry;
+
+/// Directories with the same files, names and subdirectories below them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdenticalDirs {
+    /// Merkle hash of each directory
+    pub hash: String,
+    /// Files below each directory
+    pub files: u64,
+    /// Bytes below each directory
+    pub bytes: u64,
+    pub dirs: Vec<String>,
+}
+
+impl IdenticalDirs {
+    /// Bytes freed by keeping a single copy.
+    pub fn redundant_bytes(&self) -> u64 {
+        self.bytes * (self.dirs.len() as u64 - 1)
+    }
+}
+
+/// A directory whose files all exist with the same content and relative
+/// paths below a larger one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubsetDir {
+    pub dir: String,
+    pub files: u64,
+    pub bytes: u64,
+    pub superset: String,
+    pub superset_files: u64,
+    pub superset_bytes: u64,
+}
+
+/// Result of [`analyze`], each list sorted by bytes, largest first.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct DirReport {
+    pub identical: Vec<IdenticalDirs>,
+    pub subsets: Vec<SubsetDir>,
+    /// `--min-size` of the scan; smaller files are missing from the inventory
+    /// and were not compared
+    pub scan_min_size: u64,
+}
+
+/// Hash and totals of one directory.
+struct DirNode {
+    hash: blake3::Hash,
+    files: u64,
+    bytes: u64,
+}
+
+/// Children of one directory, by name.
+#[derive(Default)]
+struct Children<'a> {
+    files: Vec<(&'a str, &'a FileEntry)>,
+    dirs: BTreeSet<&'a str>,
+}
+
+/// Find identical and contained directories below the common root of
+/// `files`. Only the files in the inventory count, so files skipped by the
+/// scan's `--min-size` don't make directories differ.
+///
+/// Each directory is hashed from the sorted names, sizes and checksums of its
+/// files and the names and hashes of its subdirectories. Only the topmost
+/// directories of an identical subtree or a contained pair are reported.
+pub fn analyze(files: &[FileEntry]) -> DirReport {
+    let Some(root) = common_root(files) else {
+        return DirReport::default();
+    };
+
+    // ---- build the tree up to the common root
+    let mut tree: BTreeMap<&str, Children> = BTreeMap::new();
+    for file in files {
+        let Some((dir, name)) = file.path.rsplit_once('/') else {
+            continue;
+        };
+        tree.entry(dir).or_default().files.push((name, file));
+        let mut dir = dir;
+        while dir.len() > root.len() {
+            let Some((parent, name)) = dir.rsplit_once('/') else {
+                break;
+            };
+            if !tree.entry(parent).or_default().dirs.insert(name) {
+                break;
+            }
+            dir = parent;
+        }
+    }
+
+    // ---- hash bottom-up, deepest directories first
+    let mut order: Vec<&str> = tree.keys().copied().collect();
+    order.sort_by_key(|dir| std::cmp::Reverse(dir.matches('/').count()));
+    let mut nodes: HashMap<&str, DirNode> = HashMap::with_capacity(order.len());
+    for dir in order {
+        let children = &tree[dir];
+        let mut lines: Vec<(&str, String)> = Vec::new();
+        let (mut count, mut bytes) = (0, 0);
+        for (name, file) in &children.files {
+            lines.push((name, format!("f\0{}\0{}\0{}\n", name, file.size, file.checksum)));
+            count += 1;
+            bytes += file.size;
+        }
+        for name in &children.dirs {
+            let sub = &nodes[format!("{}/{}", dir, name).as_str()];
+            lines.push((name, format!("d\0{}\0{}\n", name, sub.hash.to_hex())));
+            count += sub.files;
+            bytes += sub.bytes;
+        }
+        lines.sort();
+
+        let mut hasher = blake3::Hasher::new();
+        for (_, line) in &lines {
+            hasher.update(line.as_bytes());
+        }
+        nodes.insert(dir, DirNode {
+            hash: hasher.finalize(),
+            files: count,
+            bytes,
+        });
+    }
+
+    DirReport {
+        identical: identical(&nodes),
+        subsets: subsets(files, &nodes),
+        scan_min_size: 0,
+    }
+}
+
+fn identical(nodes: &HashMap<&str, DirNode>) -> Vec<IdenticalDirs> {
+    let mut by_hash: HashMap<blake3::Hash, Vec<&str>> = HashMap::new();
+    for (dir, node) in nodes {
+        by_hash.entry(node.hash).or_default().push(dir);
+    }
+
+    let mut identical: Vec<IdenticalDirs> = by_hash
+        .into_iter()
+        .filter(|(_, dirs)| dirs.len() > 1)
+        .filter(|(_, dirs)| {
+            // covered by the group of their parents if those are identical too
+            let parents: BTreeSet<&str> = dirs.iter().filter_map(|d| parent(d)).collect();
+            let hashes: HashSet<Option<blake3::Hash>> =
+                parents.iter().map(|p| nodes.get(p).map(|n| n.hash)).collect();
+            parents.len() < dirs.len() || hashes.len() > 1 || hashes.contains(&None)
+        })
+        .map(|(hash, mut dirs)| {
+            dirs.sort();
+            let node = &nodes[dirs[0]];
+            IdenticalDirs {
+                hash: hash.to_hex().to_string(),
+                files: node.files,
+                bytes: node.bytes,
+                dirs: dirs.into_iter().map(str::to_string).collect(),
+            }
+        })
+        .collect();
+    identical.sort_by(|a, b| {
+        b.redundant_bytes()
+            .cmp(&a.redundant_bytes())
+            .then_with(|| a.dirs.cmp(&b.dirs))
+    });
+    identical
+}
+
+fn subsets(files: &[FileEntry], nodes: &HashMap<&str, DirNode>) -> Vec<SubsetDir> {
+    let mut sorted: Vec<&FileEntry> = files.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+    let by_path: HashMap<&str, (u64, &str)> = sorted
+        .iter()
+        .map(|f| (f.path.as_str(), (f.size, f.checksum.as_str())))
+        .collect();
+    let mut by_content: HashMap<(u64, &str), Vec<&str>> = HashMap::new();
+    for f in &sorted {
+        by_content.entry((f.size, f.checksum.as_str())).or_default().push(&f.path);
+    }
+
+    let mut pairs: HashSet<(&str, &str)> = HashSet::new();
+    for (&dir, node) in nodes {
+        // files below `dir`, '0' sorts right after '/'
+        let (from, to) = (format!("{}/", dir), format!("{}0", dir));
+        let start = sorted.partition_point(|f| f.path < from);
+        let end = sorted.partition_point(|f| f.path < to);
+        let below = &sorted[start..end];
+        let Some(first) = below.first() else {
+            continue;
+        };
+
+        // a superset must have a copy of the first file at the same relative path
+        let relative = &first.path[dir.len() + 1..];
+        for &other in &by_content[&(first.size, first.checksum.as_str())] {
+            let Some(candidate) = other
+                .strip_suffix(relative)
+                .and_then(|rest| rest.strip_suffix('/'))
+            else {
+                continue;
+            };
+            if candidate == dir
+                || is_below(candidate, dir)
+                || is_below(dir, candidate)
+                || pairs.contains(&(dir, candidate))
+            {
+                continue;
+            }
+            let Some(superset) = nodes.get(candidate) else {
+                continue;
+            };
+            if superset.files <= node.files {
+                continue;
+            }
+            let contained = below.iter().all(|f| {
+                let path = format!("{}{}", candidate, &f.path[dir.len()..]);
+                by_path.get(path.as_str()) == Some(&(f.size, f.checksum.as_str()))
+            });
+            if contained {
+                pairs.insert((dir, candidate));
+            }
+        }
+    }
+
+    let mut subsets: Vec<SubsetDir> = pairs
+        .iter()
+        .filter(|(dir, superset)| {
+            // covered by the pair of their parents if they have the same name
+            let parents = parent(dir).zip(parent(superset));
+            !(name(dir) == name(superset) && parents.is_some_and(|p| pairs.contains(&p)))
+        })
+        .map(|&(dir, superset)| {
+            let (node, larger) = (&nodes[dir], &nodes[superset]);
+            SubsetDir {
+                dir: dir.to_string(),
+                files: node.files,
+                bytes: node.bytes,
+                superset: superset.to_string(),
+                superset_files: larger.files,
+                superset_bytes: larger.bytes,
+            }
+        })
+        .collect();
+    subsets.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.dir.cmp(&b.dir))
+            .then_with(|| a.superset.cmp(&b.superset))
+    });
+    subsets
+}
+
+/// Deepest directory containing all files, e.g. the scan root.
+fn common_root(files: &[FileEntry]) -> Option<&str> {
+    let mut root = parent(&files.first()?.path)?;
+    for file in files {
+        while !is_below(&file.path, root) {
+            root = parent(root)?;
+        }
+    }
+    Some(root)
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rsplit_once('/').map(|(parent, _)| parent)
+}
+
+fn name(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
+
+/// Whether `path` lies somewhere below the directory `dir`.
+pub fn is_below(path: &str, dir: &str) -> bool {
+    path.len() > dir.len() + 1 && path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/'
+}
+
+/// One side of a [`FolderOverlap`].
+#[derive(Serialize, Debug, Clone)]
+pub struct FolderShare {
+    pub dir: String,
+    /// Files directly in the folder
+    pub files: u64,
+    /// Bytes directly in the folder
+    pub bytes: u64,
+    /// Bytes of its files that have a copy in the other folder
+    pub shared_bytes: u64,
+    /// `shared_bytes` as a percentage of `bytes`
+    pub percent: f64,
+}
+
+/// Two folders holding copies of the same files.
+#[derive(Serialize, Debug, Clone)]
+pub struct FolderOverlap {
+    /// Duplicate groups with files in both folders
+    pub groups: u64,
+    /// Size of one copy of each shared file, freed by keeping one folder
+    pub bytes: u64,
+    pub folders: [FolderShare; 2],
+}
+
+/// Pairs of folders sharing duplicate groups, most shared bytes first.
+/// Folders are the direct parents of the files, as in `dedup clean`.
+///
+/// The folder totals behind the percentages come from `inventory` if given,
+/// otherwise from the files in `groups`, i.e. they only count duplicates.
+pub fn overlaps(groups: &[Vec<FileEntry>], inventory: Option<&[FileEntry]>) -> Vec<FolderOverlap> {
+    let mut totals: HashMap<&str, (u64, u64)> = HashMap::new();
+    let all = match inventory {
+        Some(files) => files.iter().collect::<Vec<_>>(),
+        None => groups.iter().flatten().collect(),
+    };
+    for file in all {
+        if let Some(dir) = parent(&file.path) {
+            let total = totals.entry(dir).or_default();
+            total.0 += 1;
+            total.1 += file.size;
+        }
+    }
+
+    // (groups, bytes, shared bytes of the first and second folder)
+    let mut pairs: HashMap<(&str, &str), (u64, u64, u64, u64)> = HashMap::new();
+    for group in groups {
+        let Some(size) = group.first().map(|f| f.size) else {
+            continue;
+        };
+        let mut per_dir: BTreeMap<&str, u64> = BTreeMap::new();
+        for file in group {
+            if let Some(dir) = parent(&file.path) {
+                *per_dir.entry(dir).or_default() += file.size;
+            }
+        }
+        let dirs: Vec<(&str, u64)> = per_dir.into_iter().collect();
+        for (i, &(a, a_bytes)) in dirs.iter().enumerate() {
+            for &(b, b_bytes) in &dirs[i + 1..] {
+                let pair = pairs.entry((a, b)).or_default();
+                pair.0 += 1;
+                pair.1 += size;
+                pair.2 += a_bytes;
+                pair.3 += b_bytes;
+            }
+        }
+    }
+
+    let share = |dir: &str, shared_bytes: u64| {
+        let (files, bytes) = totals.get(dir).copied().unwrap_or_default();
+        FolderShare {
+            dir: dir.to_string(),
+            files,
+            bytes,
+            shared_bytes,
+            percent: if bytes == 0 {
+                0.0
+            } else {
+                (shared_bytes as f64 / bytes as f64 * 100.0).min(100.0)
+            },
+        }
+    };
+    let mut overlaps: Vec<FolderOverlap> = pairs
+        .into_iter()
+        .map(|((a, b), (groups, bytes, a_shared, b_shared))| FolderOverlap {
+            groups,
+            bytes,
+            folders: [share(a, a_shared), share(b, b_shared)],
+        })
+        .collect();
+    overlaps.sort_by(|x, y| {
+        y.bytes
+            .cmp(&x.bytes)
+            .then_with(|| y.groups.cmp(&x.groups))
+            .then_with(|| x.folders[0].dir.cmp(&y.folders[0].dir))
+            .then_with(|| x.folders[1].dir.cmp(&y.folders[1].dir))
+    });
+    overlaps
+}
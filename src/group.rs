@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::records;
+use crate::types::FileEntry;
+
+/// Default for [`group_file`]'s memory budget.
+pub const DEFAULT_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// Upper bound for the number of temporary bucket files, stays well below
+/// the usual open file limit.
+const MAX_BUCKETS: u64 = 512;
+
+/// Group files by `(size, checksum)` and keep the groups with more than one
+/// member, largest files first.
+pub fn group(files: &[FileEntry]) -> Vec<Vec<FileEntry>> {
+    let mut map: HashMap<(u64, &str), Vec<FileEntry>> = HashMap::new();
+
+    for f in files {
+        map.entry((f.size, f.checksum.as_str()))
+            .or_default()
+            .push(f.clone());
+    }
+
+    let mut duplicates: Vec<Vec<FileEntry>> = map
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+
+    // sort duplicate groups by file size
+    duplicates.sort_by_key(|group| Reverse(group[0].size));
+    duplicates
+}
+
+/// Group the inventory in `input` (JSON or NDJSON) like [`group`], using
+/// roughly at most `memory_budget` bytes of RAM, and pass each duplicate
+/// group to `on_group`.
+///
+/// Inventories that do not fit into the budget are first split into hash
+/// buckets in a temporary directory next to `input`, which are then grouped
+/// one by one. In that case groups come out sorted by file size only within
+/// each bucket; use `dedup query --sort` afterwards if the order matters.
+pub fn group_file(
+    input: &Path,
+    memory_budget: u64,
+    mut on_group: impl FnMut(Vec<FileEntry>) -> Result<()>,
+) -> Result<GroupStats> {
+    let input_size = fs::metadata(input)
+        .with_context(|| format!("Failed to read {}", input.display()))?
+        .len();
+    // parsed entries take about three times their JSON size
+    let buckets = (input_size.saturating_mul(3) / memory_budget.max(1) + 1).min(MAX_BUCKETS);
+
+    let mut stats = GroupStats::default();
+    let mut emit = |groups: Vec<Vec<FileEntry>>| -> Result<()> {
+        for group in groups {
+            stats.add(&group);
+            on_group(group)?;
+        }
+        Ok(())
+    };
+
+    if buckets <= 1 {
+        let files: Vec<FileEntry> = records::read_all(input)?;
+        emit(group(&files))?;
+        return Ok(stats);
+    }
+
+    // ---- partition by (size, checksum) so every group lands in one bucket
+    let dir = tempfile::Builder::new()
+        .prefix(".dedup-group-")
+        .tempdir_in(input.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")))
+        .context("Failed to create temporary directory")?;
+    let bucket_path = |i: u64| dir.path().join(format!("{}.ndjson", i));
+
+    let mut writers = (0..buckets)
+        .map(|i| Ok(BufWriter::with_capacity(64 * 1024, File::create(bucket_path(i))?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    records::for_each(input, |entry: FileEntry| {
+        let mut hasher = DefaultHasher::new();
+        (entry.size, entry.checksum.as_str()).hash(&mut hasher);
+        let writer = &mut writers[(hasher.finish() % buckets) as usize];
+        serde_json::to_writer(&mut *writer, &entry)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    })?;
+    for mut writer in writers {
+        writer.flush()?;
+    }
+
+    // ---- group bucket by bucket
+    for i in 0..buckets {
+        let files: Vec<FileEntry> = records::read_all(&bucket_path(i))?;
+        emit(group(&files))?;
+    }
+
+    Ok(stats)
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct GroupStats {
+    /// Files that are part of a duplicate group.
+    pub duplicate_files: u64,
+    pub duplicate_groups: u64,
+    /// Bytes freed when keeping one file per group.
+    pub potential_savings: u64,
+}
+
+impl GroupStats {
+    pub fn from_groups(groups: &[Vec<FileEntry>]) -> Self {
+        let mut stats = GroupStats::default();
+        for group in groups {
+            stats.add(group);
+        }
+        stats
+    }
+
+    /// Count one more duplicate group.
+    pub fn add(&mut self, group: &[FileEntry]) {
+        let Some(first) = group.first() else {
+            return;
+        };
+        let count = group.len() as u64;
+
+        self.duplicate_groups += 1;
+        self.duplicate_files += count;
+        self.potential_savings += first.size * (count - 1); // keep one
+    }
+}
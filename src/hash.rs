@@ -0,0 +1,116 @@
+use sha1::Digest;
+use std::fmt::Write;
+
+/// Checksum algorithms for `dedup scan --hash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum)]
+pub enum HashAlgorithm {
+    /// BLAKE3, fast and the default
+    Blake3,
+    /// SHA-256, as used by most backup manifests
+    Sha256,
+    /// SHA-1, as reported by Nextcloud
+    Sha1,
+    /// MD5, as reported by Nextcloud and older tools
+    Md5,
+    /// 64-bit xxh3, very fast but not cryptographic
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Tag in result files, lower case like the `oc:checksums` names.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Case insensitive inverse of [`HashAlgorithm::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Md5,
+            HashAlgorithm::Xxh3,
+        ]
+        .into_iter()
+        .find(|a| a.name().eq_ignore_ascii_case(name))
+    }
+}
+
+enum State {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+    Sha1(sha1::Sha1),
+    Md5(md5::Md5),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+/// Feeds the same data to several hashers, so a file is read only once.
+pub struct MultiHasher {
+    states: Vec<(HashAlgorithm, State)>,
+}
+
+impl MultiHasher {
+    /// Hashers for `algorithms` in the given order, repeated ones are dropped.
+    pub fn new(algorithms: &[HashAlgorithm]) -> Self {
+        let mut states: Vec<(HashAlgorithm, State)> = Vec::new();
+        for &algorithm in algorithms {
+            if states.iter().any(|(a, _)| *a == algorithm) {
+                continue;
+            }
+            let state = match algorithm {
+                HashAlgorithm::Blake3 => State::Blake3(Box::default()),
+                HashAlgorithm::Sha256 => State::Sha256(sha2::Sha256::new()),
+                HashAlgorithm::Sha1 => State::Sha1(sha1::Sha1::new()),
+                HashAlgorithm::Md5 => State::Md5(md5::Md5::new()),
+                HashAlgorithm::Xxh3 => State::Xxh3(Box::default()),
+            };
+            states.push((algorithm, state));
+        }
+        MultiHasher { states }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, state) in &mut self.states {
+            match state {
+                State::Blake3(h) => {
+                    h.update(data);
+                }
+                State::Sha256(h) => h.update(data),
+                State::Sha1(h) => h.update(data),
+                State::Md5(h) => h.update(data),
+                State::Xxh3(h) => h.update(data),
+            }
+        }
+    }
+
+    /// `(algorithm name, lower case hex)` in the order of [`MultiHasher::new`].
+    pub fn finalize(self) -> Vec<(String, String)> {
+        self.states
+            .into_iter()
+            .map(|(algorithm, state)| {
+                let hex = match state {
+                    State::Blake3(h) => h.finalize().to_hex().to_string(),
+                    State::Sha256(h) => to_hex(&h.finalize()),
+                    State::Sha1(h) => to_hex(&h.finalize()),
+                    State::Md5(h) => to_hex(&h.finalize()),
+                    State::Xxh3(h) => format!("{:016x}", h.digest()),
+                };
+                (algorithm.name().to_string(), hex)
+            })
+            .collect()
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(hex, "{:02x}", b);
+    }
+    hex
+}
@@ -1 +1,23 @@
+pub mod archive;
+pub mod audit;
+pub mod chunks;
+pub mod client;
+pub mod credentials;
+pub mod diff;
+pub mod dirs;
+pub mod group;
+pub mod hash;
+pub mod media;
+pub mod meta;
+pub mod output;
+pub mod quality;
+pub mod query;
+pub mod records;
+pub mod report;
+pub mod resume;
+pub mod scan;
+pub mod similar;
+pub mod store;
 pub mod types;
+pub mod video;
+pub mod webdav;
@@ -1,233 +1,86 @@
-use anyhow::{Context, Result};
-use serde::Serialize;
-use blake3::Hasher;
-use std::{
-    cmp::Reverse,
-    collections::HashMap,
-    fs::File,
-    io::{BufWriter, Read, Write},
-    path::PathBuf,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
-    },
-};
-use tokio::task;
-use walkdir::WalkDir;
-
-use dedup::types::FileEntry;
-
-
-struct Stats {
-    files: AtomicU64,
-    bytes: AtomicU64,
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let root = std::env::args()
-        .nth(1)
-        .context("Usage: dedup <directory>")?;
-
-    println!("Processing folder: {}\n", root);
-
-    let stats = Arc::new(Stats {
-        files: AtomicU64::new(0),
-        bytes: AtomicU64::new(0),
-    });
-
-    let mut tasks = Vec::new();
-
-    for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() {
-
-            let metadata = match entry.metadata() {
-                Ok(m) => m,
-                Err(_) => continue, // ignore
-            };
-
-            if metadata.len() < 1024 {
-                continue; // ignore small files
-            }
-
-            let path = entry.path().to_path_buf();
-            let stats = stats.clone();
-
-            tasks.push(task::spawn_blocking(move || {
-                process_file(path, stats)
-            }));
-        }
-    }
-
-    let mut files = Vec::new();
-
-    for task in tasks {
-        match task.await {
-            Ok(Ok(entry)) => files.push(entry),
-            Ok(Err(e)) => eprintln!("Error processing file: {}", e),
-            Err(e) => eprintln!("Task failed: {}", e),
-        }
-    }
-
-    // ---- sort all files by path
-    files.sort_by(|a, b| a.path.cmp(&b.path));
-    write_json("all_files.json", &files)?;
-
-    // ---- find duplicates
-    let mut map: HashMap<(u64, String), Vec<FileEntry>> = HashMap::new();
-
-    for f in &files {
-        map.entry((f.size, f.checksum.clone()))
-            .or_default()
-            .push(f.clone());
-    }
-
-    let mut duplicates: Vec<Vec<FileEntry>> = map
-        .into_values()
-        .filter(|group| group.len() > 1)
-        .collect();
+use anyhow::Result;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
 
-    // sort duplicate groups by file size
-    duplicates.sort_by_key(|group| Reverse(group[0].size));
-    write_json("duplicates.json", &duplicates)?;
+mod commands;
 
-    // ---- statistics
-    let total_files = stats.files.load(Ordering::Relaxed);
-    let total_bytes = stats.bytes.load(Ordering::Relaxed);
+use commands::Verbosity;
 
-    let mut duplicate_files = 0u64;
-    let mut potential_savings = 0u64;
+/// Find duplicate files and clean them up, locally or on a Nextcloud server.
+#[derive(Parser)]
+#[command(name = "dedup", version, about)]
+struct Cli {
+    /// More output, repeat for even more
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
 
-    for group in &duplicates {
-        let size = group[0].size;
-        let count = group.len() as u64;
-
-        duplicate_files += count;
-        potential_savings += size * (count - 1); // keep one
-    }
+    /// Only print errors and results
+    #[arg(short, long, global = true)]
+    quiet: bool,
 
-    println!("\n=== Statistics ===");
-    println!("Total files           : {}", total_files);
-    println!(
-        "Total data processed  : {:.2} MB",
-        total_bytes as f64 / 1_048_576.0
-    );
-    println!("Duplicate files       : {}", duplicate_files);
-    println!("Duplicate groups      : {}", duplicates.len());
-    println!(
-        "Potential savings     : {:.2} MB",
-        potential_savings as f64 / 1_048_576.0
-    );
-
-    println!("\nOutput written to:");
-    println!("  all_files.json");
-    println!("  duplicates.json");
-
-    Ok(())
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn process_file(path: PathBuf, stats: Arc<Stats>) -> Result<FileEntry> {
-    let mut file = File::open(&path)
-        .with_context(|| format!("Failed to open {}", path.display()))?;
-
-    let metadata = file.metadata()?;
-    let size = metadata.len();
-
-    let mut hasher = Hasher::new();
-    let mut buffer = vec![0u8; 2 * 1024 * 1024];
-
-    loop {
-        let n = file.read(&mut buffer)?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buffer[..n]);
-    }
-
-    let file_count = stats.files.fetch_add(1, Ordering::Relaxed) + 1;
-    let byte_count = stats.bytes.fetch_add(size, Ordering::Relaxed) + size;
-
-    if file_count % 100 == 0 {
-        println!(
-            "Processed {:>8} files ({:.2} MB)",
-            file_count,
-            byte_count as f64 / 1_048_576.0
-        );
-    }
-
-    Ok(FileEntry {
-        path: path.to_string_lossy().to_string(),
-        size,
-        checksum: hasher.finalize().to_hex().to_string(),
-                      
-    })
-}
-
-fn write_json<T: Serialize>(filename: &str, data: &T) -> Result<()> {
-    let file = File::create(filename)?;
-    let mut writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(&mut writer, data)?;
-    writer.flush()?;
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// Hash all files below a directory or WebDAV URL and find duplicates
+    Scan(commands::scan::ScanArgs),
+    /// Cluster visually similar images and videos of an inventory
+    Similar(commands::similar::SimilarArgs),
+    /// Find partially identical large files by content-defined chunking
+    Chunks(commands::chunks::ChunksArgs),
+    /// Find duplicate groups in an inventory within a memory budget
+    Group(commands::group::GroupArgs),
+    /// Combine inventories from several hosts and find duplicates across them
+    Merge(commands::merge::MergeArgs),
+    /// Filter and sort duplicate groups or inventories, e.g. the top wasted-space groups
+    Query(commands::query::QueryArgs),
+    /// Audit a duplicates file against itself and the disk, exit code 2 on findings
+    Check(commands::check::CheckArgs),
+    /// Compare two inventories: added, deleted, modified and moved files
+    Diff(commands::diff::DiffArgs),
+    /// Find identical directory trees and directories contained in others
+    Dirs(commands::dirs::DirsArgs),
+    /// Rank folder pairs by the duplicates they share, before deciding what to clean
+    Overlap(commands::overlap::OverlapArgs),
+    /// Export duplicate groups as CSV or a self-contained HTML page for review
+    Report(commands::report::ReportArgs),
+    /// Interactively delete local duplicates, one preferred directory at a time
+    Clean(commands::clean::CleanArgs),
+    /// Delete duplicates on a Nextcloud server via WebDAV according to a policy
+    RemoteClean(commands::remote_clean::RemoteCleanArgs),
+    /// Print shell completions
+    Completions {
+        shell: clap_complete::Shell,
+    },
 }
 
-/*
- * uses more than 80 gb RAM, takes longer 
- *
-fn process_file_optimized(path: PathBuf, stats: Arc<Stats>) -> Result<FileEntry> {
-    let file = File::open(&path)
-        .with_context(|| format!("Failed to open {}", path.display()))?;
-    let metadata = file.metadata()?;
-    let size = metadata.len();
-
-    // Thresholds
-    const SMALL_FILE: u64 = 1 * 1024 * 1024;   // 1 MB
-    const MEDIUM_FILE: u64 = 128 * 1024 * 1024; // 128 MB
-
-    let hash_hex = if size < SMALL_FILE {
-        // kleine Dateien: 64 KB Buffer
-        let mut hasher = Hasher::new();
-        let mut buffer = [0u8; 64 * 1024];
-        let mut f = file;
-        loop {
-            let n = f.read(&mut buffer)?;
-            if n == 0 { break; }
-            hasher.update(&buffer[..n]);
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let verbosity = Verbosity::new(cli.verbose, cli.quiet);
+
+    match cli.command {
+        Command::Scan(args) => commands::scan::run(args, verbosity).await,
+        Command::Similar(args) => commands::similar::run(args, verbosity).await,
+        Command::Chunks(args) => commands::chunks::run(args, verbosity).await,
+        Command::Group(args) => commands::group::run(args, verbosity),
+        Command::Merge(args) => commands::merge::run(args, verbosity),
+        Command::Query(args) => commands::query::run(args, verbosity),
+        Command::Check(args) => commands::check::run(args, verbosity),
+        Command::Diff(args) => commands::diff::run(args, verbosity),
+        Command::Dirs(args) => commands::dirs::run(args, verbosity),
+        Command::Overlap(args) => commands::overlap::run(args, verbosity),
+        Command::Report(args) => commands::report::run(args, verbosity),
+        Command::Clean(args) => commands::clean::run(args, verbosity),
+        Command::RemoteClean(args) => {
+            // reqwest's blocking client must not run on the async runtime
+            tokio::task::spawn_blocking(move || commands::remote_clean::run(args, verbosity))
+                .await?
         }
-        hasher.finalize().to_hex().to_string()
-    } else if size < MEDIUM_FILE {
-        // mittlere Dateien: 4 MB Buffer
-        let mut hasher = Hasher::new();
-        let mut buffer = vec![0u8; 4 * 1024 * 1024];
-        let mut f = file;
-        loop {
-            let n = f.read(&mut buffer)?;
-            if n == 0 { break; }
-            hasher.update(&buffer[..n]);
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "dedup", &mut std::io::stdout());
+            Ok(())
         }
-        hasher.finalize().to_hex().to_string()
-    } else {
-        // große Dateien: mmap
-        let mmap = unsafe { Mmap::map(&file)? };
-        blake3::hash(&mmap).to_hex().to_string()
-    };
-
-    // Statistik
-    let file_count = stats.files.fetch_add(1, Ordering::Relaxed) + 1;
-    let byte_count = stats.bytes.fetch_add(size, Ordering::Relaxed) + size;
-
-    if file_count % 100 == 0 {
-        println!(
-            "Processed {:>8} files ({:.2} MB)",
-            file_count,
-            byte_count as f64 / 1_048_576.0
-        );
     }
-
-    Ok(FileEntry {
-        path: path.to_string_lossy().to_string(),
-        size,
-        blake3: hash_hex, // optional: kannst auch field umbenennen
-    })
 }
-*/
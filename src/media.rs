@@ -0,0 +1,333 @@
+use chrono::{DateTime, Local, NaiveDateTime};
+use exif::{In, Reader, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::similar;
+
+/// Extensions that may carry EXIF data.
+const EXIF_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tif", "tiff", "png", "webp", "heic", "heif"];
+
+/// ISO base media (MP4/QuickTime) extensions, read without ffmpeg.
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "3gp"];
+
+/// The `moov` box holds the metadata and is small; anything larger is
+/// not worth reading.
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Seconds between the MP4 epoch (1904) and the Unix epoch.
+const MP4_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Capture metadata of an image or video, see `dedup scan --media`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+    /// When the picture or video was taken, in the camera's local time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Video length in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// Camera maker from EXIF.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    /// Camera model from EXIF.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The file has EXIF data at all.
+    #[serde(default)]
+    pub exif: bool,
+    /// The file records a GPS position.
+    #[serde(default)]
+    pub gps: bool,
+}
+
+impl MediaInfo {
+    /// Read what can be read from the image or video at `path`; anything
+    /// unreadable stays unset, other files give `None`.
+    pub fn read(path: &Path) -> Option<MediaInfo> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        let mut info = MediaInfo::default();
+
+        if VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+            if let Ok(file) = File::open(path) {
+                let _ = read_mp4(&mut BufReader::new(file), &mut info);
+            }
+            return Some(info);
+        }
+
+        let image = similar::is_image(&path.to_string_lossy());
+        if !image && !EXIF_EXTENSIONS.contains(&ext.as_str()) {
+            return None;
+        }
+        if image {
+            if let Ok((width, height)) = image::image_dimensions(path) {
+                info.width = Some(width);
+                info.height = Some(height);
+            }
+        }
+        if let Ok(file) = File::open(path) {
+            if let Ok(exif) = Reader::new().read_from_container(&mut BufReader::new(file)) {
+                read_exif(&exif, &mut info);
+            }
+        }
+        Some(info)
+    }
+
+    /// Width times height, if known.
+    pub fn pixels(&self) -> Option<u64> {
+        Some(self.width? as u64 * self.height? as u64)
+    }
+
+    /// `Canon EOS 5D`, without repeating the maker if the model contains it.
+    pub fn camera(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (Some(one), None) | (None, Some(one)) => Some(one.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+fn read_exif(exif: &exif::Exif, info: &mut MediaInfo) {
+    info.exif = true;
+    info.make = ascii_field(exif, Tag::Make);
+    info.model = ascii_field(exif, Tag::Model);
+    info.gps = exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some();
+    info.captured_at = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .into_iter()
+        .filter_map(|tag| ascii_field(exif, tag))
+        .find_map(|text| NaiveDateTime::parse_from_str(&text, "%Y:%m:%d %H:%M:%S").ok());
+
+    // HEIC and friends can't be decoded, but EXIF knows the size
+    if info.width.is_none() {
+        info.width = uint_field(exif, Tag::PixelXDimension);
+        info.height = uint_field(exif, Tag::PixelYDimension);
+    }
+}
+
+fn ascii_field(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(values.first()?);
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn uint_field(exif: &exif::Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+// ---- MP4 / QuickTime
+
+/// Fill `info` from the `moov` box: `mvhd` has creation time and duration,
+/// the first video `tkhd` the dimensions and `udta/©xyz` the location.
+fn read_mp4<R: Read + Seek>(reader: &mut R, info: &mut MediaInfo) -> std::io::Result<()> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut pos: u64 = 0;
+    while pos + 8 <= end {
+        reader.seek(SeekFrom::Start(pos))?;
+        let (kind, header, size) = read_box_header(reader, end - pos)?;
+        if kind == *b"moov" {
+            if size - header > MAX_MOOV_SIZE {
+                return Ok(());
+            }
+            let mut moov = vec![0u8; (size - header) as usize];
+            reader.read_exact(&mut moov)?;
+            parse_moov(&moov, info);
+            return Ok(());
+        }
+        // truncated or crafted files must not wrap the position around
+        match pos.checked_add(size) {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// `(type, header length, box length)`; a length of 0 means "up to the end".
+fn read_box_header<R: Read>(reader: &mut R, remaining: u64) -> std::io::Result<([u8; 4], u64, u64)> {
+    let mut head = [0u8; 8];
+    reader.read_exact(&mut head)?;
+    let kind = [head[4], head[5], head[6], head[7]];
+    let (header, size) = match u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as u64 {
+        0 => (8, remaining),
+        1 => {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            (16, u64::from_be_bytes(large))
+        }
+        size => (8, size),
+    };
+    if size < header || size > remaining {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "bad box size"));
+    }
+    Ok((kind, header, size))
+}
+
+/// Child boxes of `data` as `(type, content)`.
+fn boxes(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut result = Vec::new();
+    let mut rest = data;
+    while rest.len() >= 8 {
+        let mut cursor = rest;
+        let Ok((kind, header, size)) = read_box_header(&mut cursor, rest.len() as u64) else {
+            break;
+        };
+        let Some(content) = rest.get(header as usize..size as usize) else {
+            break;
+        };
+        result.push((kind, content));
+        rest = &rest[size as usize..];
+    }
+    result
+}
+
+fn parse_moov(moov: &[u8], info: &mut MediaInfo) {
+    for (kind, content) in boxes(moov) {
+        match &kind {
+            b"mvhd" => parse_mvhd(content, info),
+            b"trak" if info.width.is_none() => {
+                if let Some((_, tkhd)) = boxes(content).into_iter().find(|(k, _)| k == b"tkhd") {
+                    parse_tkhd(tkhd, info);
+                }
+            }
+            b"udta" => {
+                info.gps |= boxes(content).iter().any(|(k, _)| k == b"\xa9xyz");
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_mvhd(data: &[u8], info: &mut MediaInfo) {
+    let (created, timescale, duration) = match data.first() {
+        Some(1) if data.len() >= 32 => (be_u64(&data[4..]), be_u32(&data[20..]) as u64, be_u64(&data[24..])),
+        Some(0) if data.len() >= 20 => (
+            be_u32(&data[4..]) as u64,
+            be_u32(&data[12..]) as u64,
+            be_u32(&data[16..]) as u64,
+        ),
+        _ => return,
+    };
+    if timescale > 0 {
+        info.duration = Some(duration as f64 / timescale as f64);
+    }
+    if created > 0 {
+        // stored in UTC, shown like camera time
+        info.captured_at = DateTime::from_timestamp(created as i64 - MP4_EPOCH_OFFSET, 0)
+            .map(|t| t.with_timezone(&Local).naive_local());
+    }
+}
+
+fn parse_tkhd(data: &[u8], info: &mut MediaInfo) {
+    let offset = match data.first() {
+        Some(1) => 88,
+        Some(0) => 76,
+        _ => return,
+    };
+    let Some(size) = data.get(offset..offset + 8) else {
+        return;
+    };
+    // 16.16 fixed point, audio tracks have none
+    let (width, height) = (be_u32(size) >> 16, be_u32(&size[4..]) >> 16);
+    if width > 0 && height > 0 {
+        info.width = Some(width);
+        info.height = Some(height);
+    }
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn be_u64(data: &[u8]) -> u64 {
+    (be_u32(data) as u64) << 32 | be_u32(&data[4..]) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(content);
+        data
+    }
+
+    fn mvhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut content = vec![0u8; 12];
+        content.extend_from_slice(&timescale.to_be_bytes());
+        content.extend_from_slice(&duration.to_be_bytes());
+        mp4_box(b"mvhd", &content)
+    }
+
+    fn tkhd(width: u32, height: u32) -> Vec<u8> {
+        let mut content = vec![0u8; 76];
+        content.extend_from_slice(&(width << 16).to_be_bytes());
+        content.extend_from_slice(&(height << 16).to_be_bytes());
+        mp4_box(b"tkhd", &content)
+    }
+
+    #[test]
+    fn mp4_reads_duration_and_dimensions() {
+        let moov = [mvhd(1000, 5000), mp4_box(b"trak", &tkhd(1920, 1080))].concat();
+        let file = [mp4_box(b"ftyp", b"isom"), mp4_box(b"moov", &moov)].concat();
+        let mut info = MediaInfo::default();
+        read_mp4(&mut Cursor::new(file), &mut info).unwrap();
+        assert_eq!(info.duration, Some(5.0));
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+        assert_eq!(info.captured_at, None);
+    }
+
+    #[test]
+    fn mp4_truncated_box_is_an_error() {
+        let header = &mp4_box(b"moov", &[])[..5];
+        assert!(read_box_header(&mut Cursor::new(header), 5).is_err());
+        // a 64-bit size announced but not present
+        let mut large = 1u32.to_be_bytes().to_vec();
+        large.extend_from_slice(b"moov");
+        assert!(read_box_header(&mut Cursor::new(&large), 16).is_err());
+    }
+
+    #[test]
+    fn mp4_box_larger_than_the_file_is_rejected() {
+        let mut file = mp4_box(b"ftyp", b"isom");
+        file.extend_from_slice(&1000u32.to_be_bytes());
+        file.extend_from_slice(b"moov");
+        let mut info = MediaInfo::default();
+        assert!(read_mp4(&mut Cursor::new(file), &mut info).is_err());
+        assert_eq!(info, MediaInfo::default());
+
+        // children running past their parent are dropped, not sliced
+        let mut moov = mvhd(1000, 5000);
+        moov.extend_from_slice(&mp4_box(b"trak", &tkhd(1920, 1080))[..40]);
+        let kinds: Vec<_> = boxes(&moov).into_iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, vec![*b"mvhd"]);
+    }
+
+    #[test]
+    fn mp4_overflowing_64_bit_size_is_rejected() {
+        let mut file = 1u32.to_be_bytes().to_vec();
+        file.extend_from_slice(b"mdat");
+        file.extend_from_slice(&u64::MAX.to_be_bytes());
+        file.extend_from_slice(&mp4_box(b"moov", &mvhd(1000, 5000)));
+        assert!(read_box_header(&mut Cursor::new(&file), file.len() as u64).is_err());
+        let mut info = MediaInfo::default();
+        assert!(read_mp4(&mut Cursor::new(file), &mut info).is_err());
+        assert_eq!(info.duration, None);
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Version of the result file layout, bumped on incompatible changes.
+///
+/// * 0: bare JSON array, no metadata
+/// * 1: `{"format_version", "meta", "records"}` envelope; NDJSON files start
+///   with a `{"format_version", "meta"}` header line
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How and where a result file was produced.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanMeta {
+    pub run_id: String,
+    pub roots: Vec<String>,
+    pub options: ScanSettings,
+    pub hash_algorithm: String,
+    pub started_at: DateTime<FixedOffset>,
+    pub finished_at: DateTime<FixedOffset>,
+    pub hostname: String,
+    pub tool_version: String,
+}
+
+/// The filters and options a scan ran with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ScanSettings {
+    pub min_size: u64,
+    pub follow_links: bool,
+    /// How remote files were hashed, see `RemoteHashing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_hashing: Option<String>,
+    /// Algorithms of `scan --hash`, the first one gives the checksum.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<String>,
+    /// Whether archive members were scanned as virtual files.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archives: bool,
+}
+
+/// Envelope header as written in front of the records.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Header {
+    pub format_version: u32,
+    pub meta: ScanMeta,
+}
+
+impl ScanMeta {
+    /// Metadata for a scan of `roots` that started at `started_at` and ends now.
+    pub fn new(
+        roots: Vec<String>,
+        options: ScanSettings,
+        hash_algorithm: &str,
+        started_at: DateTime<FixedOffset>,
+    ) -> Self {
+        ScanMeta {
+            run_id: format!("{}-{}", started_at.format("%Y%m%dT%H%M%S"), std::process::id()),
+            roots,
+            options,
+            hash_algorithm: hash_algorithm.to_string(),
+            started_at,
+            finished_at: now(),
+            hostname: local_hostname(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Reasons not to trust this input for deleting files.
+    ///
+    /// The host only matters for local roots, remote URLs look the same from
+    /// everywhere.
+    pub fn concerns(&self, max_age: Duration) -> Vec<String> {
+        let mut concerns = Vec::new();
+
+        let age = (now() - self.finished_at).to_std().unwrap_or_default();
+        if age > max_age {
+            concerns.push(format!(
+                "scan {} finished {} ago ({}), older than {}",
+                self.run_id,
+                format_age(age),
+                self.finished_at.format("%Y-%m-%d %H:%M"),
+                format_age(max_age)
+            ));
+        }
+
+        let local_roots = self
+            .roots
+            .iter()
+            .any(|r| !r.starts_with("http://") && !r.starts_with("https://"));
+        let host = local_hostname();
+        if local_roots && self.hostname != host {
+            concerns.push(format!(
+                "scan {} ran on host {}, this is {}",
+                self.run_id, self.hostname, host
+            ));
+        }
+
+        concerns
+    }
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    Local::now().fixed_offset()
+}
+
+pub fn local_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Parse ages like `90s`, `30m`, `12h`, `7d` or `2w`; plain numbers are seconds.
+pub fn parse_age(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().context("expected a number like 12h or 7d")?;
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("unknown unit {:?}, use s, m, h, d or w", unit),
+    };
+    let secs = number
+        .checked_mul(factor)
+        .with_context(|| format!("{} is too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        s if s >= 2 * 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 2 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 2 * 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!(parse_age("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_age(" 12h ").unwrap(), Duration::from_secs(12 * 60 * 60));
+        assert_eq!(parse_age("7d").unwrap(), Duration::from_secs(7 * 24 * 60 * 60));
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 24 * 60 * 60));
+    }
+
+    #[test]
+    fn rejects_malformed_ages() {
+        for age in ["", "d", "7y", "7 d", "7dd", "-1d", "1.5h"] {
+            assert!(parse_age(age).is_err(), "{:?}", age);
+        }
+        assert!(format!("{:#}", parse_age("7y").unwrap_err()).contains("unknown unit"));
+    }
+
+    #[test]
+    fn rejects_overflowing_ages() {
+        assert!(parse_age("999999999999999w").is_err());
+        assert!(parse_age("99999999999999999999").is_err());
+        assert!(parse_age(&format!("{}s", u64::MAX)).is_ok());
+    }
+}
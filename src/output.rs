@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where result files of a run go: `<dir>/<prefix><name>`.
+#[derive(Debug, Clone)]
+pub struct OutputLocation {
+    pub dir: PathBuf,
+    pub prefix: String,
+}
+
+impl OutputLocation {
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        OutputLocation {
+            dir: dir.into(),
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Put the run into a new subdirectory named after the local start time,
+    /// e.g. `<dir>/20240131-174502`.
+    pub fn timestamped(self) -> Self {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        OutputLocation {
+            dir: self.dir.join(stamp),
+            prefix: self.prefix,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", self.prefix, name))
+    }
+
+    /// Create the output directory if needed.
+    pub fn create_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))
+    }
+}
+
+/// Write `data` as pretty JSON via a temp file in the same directory plus
+/// rename, so readers never see a half written file.
+pub fn write_json_atomic<T: Serialize>(path: &Path, data: &T) -> Result<()> {
+    write_atomic(path, |writer| {
+        serde_json::to_writer_pretty(&mut *writer, data)?;
+        Ok(())
+    })
+}
+
+/// Write `path` through `write` into a temp file, then rename it into place.
+pub fn write_atomic(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    write(file.writer())?;
+    file.commit()
+}
+
+/// A file that only appears under its final name once [`AtomicFile::commit`]
+/// succeeds. Dropping it uncommitted removes the temp file.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("{} is not a file path", path.display()))?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(format!(".tmp-{}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            tmp_path,
+            writer: Some(BufWriter::with_capacity(1024 * 1024, file)),
+        })
+    }
+
+    pub fn writer(&mut self) -> &mut BufWriter<File> {
+        self.writer.as_mut().expect("file is not committed yet")
+    }
+
+    /// Flush, sync and rename the temp file into place.
+    pub fn commit(mut self) -> Result<()> {
+        let mut writer = self.writer.take().expect("file is not committed yet");
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+
+        fs::rename(&self.tmp_path, &self.path).with_context(|| {
+            let _ = fs::remove_file(&self.tmp_path);
+            format!("Failed to move {} into place", self.path.display())
+        })
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use crate::archive;
+use crate::media::MediaInfo;
+use crate::types::FileEntry;
+
+/// Path parts of messenger downloads, compared in lower case.
+const MESSENGER_MARKERS: &[&str] = &[
+    "whatsapp", "telegram", "signal", "threema", "messenger", "wechat", "viber", "-wa0",
+];
+
+/// What makes one copy of an image better than another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Criterion {
+    /// Most pixels
+    Resolution,
+    /// Largest file
+    Size,
+    /// Camera make or model in the EXIF data
+    Camera,
+    /// GPS position, or at least EXIF data
+    Metadata,
+    /// Not saved by a messenger (WhatsApp, Telegram, Signal, …)
+    OriginalPath,
+}
+
+/// Criteria in the order they are applied when none are given. Size comes
+/// last: a lossless conversion of a camera JPEG is larger but no better.
+pub const DEFAULT_CRITERIA: &[Criterion] = &[
+    Criterion::Resolution,
+    Criterion::Camera,
+    Criterion::Metadata,
+    Criterion::OriginalPath,
+    Criterion::Size,
+];
+
+/// Indices of the best members of `group`: compared by the first criterion,
+/// ties by the next one and so on. Several members are returned if they are
+/// equal in every criterion.
+///
+/// Image properties come from `FileEntry::media` or are read from local
+/// files; remote files without them only compare by size and path.
+pub fn best_members(group: &[FileEntry], criteria: &[Criterion]) -> Vec<usize> {
+    let scores: Vec<Vec<u64>> = group
+        .iter()
+        .map(|entry| {
+            let info = media_info(entry);
+            criteria.iter().map(|c| score(*c, entry, &info)).collect()
+        })
+        .collect();
+
+    let Some(best) = scores.iter().max() else {
+        return Vec::new();
+    };
+    scores
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| *s == best)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Whether `path` looks like a messenger download.
+pub fn is_messenger_path(path: &str) -> bool {
+    let path = path.to_lowercase();
+    MESSENGER_MARKERS.iter().any(|m| path.contains(m))
+}
+
+fn media_info(entry: &FileEntry) -> MediaInfo {
+    if let Some(media) = &entry.media {
+        return media.clone();
+    }
+    if entry.path.starts_with("http://") || entry.path.starts_with("https://") || archive::is_member(&entry.path) {
+        return MediaInfo::default();
+    }
+    MediaInfo::read(Path::new(&entry.path)).unwrap_or_default()
+}
+
+/// Higher is better.
+fn score(criterion: Criterion, entry: &FileEntry, info: &MediaInfo) -> u64 {
+    match criterion {
+        Criterion::Resolution => info.pixels().unwrap_or(0),
+        Criterion::Size => entry.size,
+        Criterion::Camera => u64::from(info.make.is_some() || info.model.is_some()),
+        Criterion::Metadata => u64::from(info.exif) + u64::from(info.gps),
+        Criterion::OriginalPath => u64::from(!is_messenger_path(&entry.path)),
+    }
+}
@@ -0,0 +1,142 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::cmp::{Ordering, Reverse};
+
+use crate::types::FileEntry;
+
+/// One record of either result file shape.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Record {
+    Group(Vec<FileEntry>),
+    File(Box<FileEntry>),
+}
+
+/// What to order by. Numbers and times sort largest/newest first, paths
+/// alphabetically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// File size
+    Size,
+    /// Bytes freed by keeping one copy (groups only)
+    Wasted,
+    /// Number of copies (groups only)
+    Count,
+    /// Path of the (first) file
+    Path,
+    /// Modification time, newest member of a group
+    Mtime,
+}
+
+impl SortKey {
+    pub fn needs_groups(self) -> bool {
+        matches!(self, SortKey::Wasted | SortKey::Count)
+    }
+}
+
+/// Conditions a file or group has to meet. A group matches if its members
+/// meet the size and count limits and at least one of them matches the path
+/// conditions.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub prefix: Option<String>,
+    pub regex: Option<Regex>,
+    pub min_size: Option<u64>,
+    /// Lower case extensions without the dot.
+    pub extensions: Vec<String>,
+    pub min_count: Option<usize>,
+    pub max_count: Option<usize>,
+}
+
+impl Filter {
+    pub fn needs_groups(&self) -> bool {
+        self.min_count.is_some() || self.max_count.is_some()
+    }
+
+    pub fn matches_file(&self, file: &FileEntry) -> bool {
+        self.min_size.is_none_or(|min| file.size >= min) && self.matches_path(&file.path)
+    }
+
+    pub fn matches_group(&self, group: &[FileEntry]) -> bool {
+        let Some(first) = group.first() else {
+            return false;
+        };
+        self.min_size.is_none_or(|min| first.size >= min)
+            && self.min_count.is_none_or(|min| group.len() >= min)
+            && self.max_count.is_none_or(|max| group.len() <= max)
+            && group.iter().any(|f| self.matches_path(&f.path))
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        if let Some(prefix) = &self.prefix {
+            if !path.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(path) {
+                return false;
+            }
+        }
+        if !self.extensions.is_empty() {
+            let ext = path
+                .rsplit('/')
+                .next()
+                .and_then(|name| name.rsplit_once('.'))
+                .map(|(_, ext)| ext.to_ascii_lowercase());
+            if !ext.is_some_and(|ext| self.extensions.contains(&ext)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub fn sort_files(files: &mut [FileEntry], key: SortKey, reverse: bool) {
+    files.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Path => a.path.cmp(&b.path),
+            SortKey::Mtime => b.mtime.cmp(&a.mtime),
+            _ => b.size.cmp(&a.size),
+        };
+        maybe_reverse(order, reverse)
+    });
+}
+
+pub fn sort_groups(groups: &mut [Vec<FileEntry>], key: SortKey, reverse: bool) {
+    groups.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Size => Reverse(group_size(a)).cmp(&Reverse(group_size(b))),
+            SortKey::Wasted => Reverse(wasted(a)).cmp(&Reverse(wasted(b))),
+            SortKey::Count => b.len().cmp(&a.len()),
+            SortKey::Path => first_path(a).cmp(first_path(b)),
+            SortKey::Mtime => newest(b).cmp(&newest(a)),
+        };
+        maybe_reverse(order, reverse)
+    });
+}
+
+/// Bytes freed by keeping one file of the group.
+pub fn wasted(group: &[FileEntry]) -> u64 {
+    group_size(group) * (group.len().max(1) as u64 - 1)
+}
+
+fn group_size(group: &[FileEntry]) -> u64 {
+    group.first().map_or(0, |f| f.size)
+}
+
+fn first_path(group: &[FileEntry]) -> &str {
+    group.iter().map(|f| f.path.as_str()).min().unwrap_or_default()
+}
+
+fn newest(group: &[FileEntry]) -> Option<i64> {
+    group.iter().filter_map(|f| f.mtime).max()
+}
+
+fn maybe_reverse(order: Ordering, reverse: bool) -> Ordering {
+    if reverse {
+        order.reverse()
+    } else {
+        order
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{bail, Context, Result};
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Serialize,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+use crate::meta::{Header, ScanMeta, FORMAT_VERSION};
+use crate::output::AtomicFile;
+
+/// Error message used to stop the JSON parser once the metadata is found.
+const STOP: &str = "dedup: stop reading";
+
+/// On-disk format of result files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// One pretty printed JSON array
+    Json,
+    /// Newline-delimited JSON, one record per line
+    Ndjson,
+}
+
+impl Format {
+    /// `.ndjson` and `.jsonl` files are NDJSON, everything else is JSON.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ndjson") | Some("jsonl") => Format::Ndjson,
+            _ => Format::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Call `f` for every record in `path` without loading the whole file.
+///
+/// Records are the elements of the `records` array (or of the top level
+/// array of files without metadata) for JSON, or the lines after the header
+/// for NDJSON.
+pub fn for_each<T: DeserializeOwned>(path: &Path, mut f: impl FnMut(T) -> Result<()>) -> Result<()> {
+    let reader = open(path)?;
+
+    match Format::from_path(path) {
+        Format::Ndjson => {
+            let mut first = true;
+            for (lineno, line) in reader.lines().enumerate() {
+                let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let context = || format!("{}:{}: invalid record", path.display(), lineno + 1);
+
+                if std::mem::take(&mut first) {
+                    let value: serde_json::Value = serde_json::from_str(&line).with_context(context)?;
+                    if let Some(version) = value.get("format_version") {
+                        check_version(path, version.as_u64().unwrap_or(u64::MAX))?;
+                        continue;
+                    }
+                    f(serde_json::from_value(value).with_context(context)?)?;
+                } else {
+                    f(serde_json::from_str(&line).with_context(context)?)?;
+                }
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let mut callback_error = None;
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            let visitor = RecordsVisitor {
+                path,
+                f: &mut f,
+                error: &mut callback_error,
+                marker: PhantomData,
+            };
+            let result = serde::Deserializer::deserialize_any(&mut de, visitor).and_then(|_| de.end());
+
+            if let Some(e) = callback_error {
+                return Err(e);
+            }
+            result.with_context(|| format!("Invalid JSON in {}", path.display()))
+        }
+    }
+}
+
+/// Metadata of `path`, `None` for files written before the envelope existed.
+pub fn read_meta(path: &Path) -> Result<Option<ScanMeta>> {
+    let reader = open(path)?;
+
+    match Format::from_path(path) {
+        Format::Ndjson => {
+            for line in reader.lines() {
+                let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value: serde_json::Value = serde_json::from_str(&line)
+                    .with_context(|| format!("{}: invalid header", path.display()))?;
+                let Some(version) = value.get("format_version") else {
+                    return Ok(None);
+                };
+                check_version(path, version.as_u64().unwrap_or(u64::MAX))?;
+                let header: Header = serde_json::from_value(value)
+                    .with_context(|| format!("{}: invalid header", path.display()))?;
+                return Ok(Some(header.meta));
+            }
+            Ok(None)
+        }
+        Format::Json => {
+            let mut found = None;
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            let result = serde::Deserializer::deserialize_any(&mut de, MetaVisitor { path, found: &mut found });
+            match (found, result) {
+                (Some(meta), _) => meta,
+                (None, Err(e)) => Err(e).with_context(|| format!("Invalid JSON in {}", path.display())),
+                (None, Ok(())) => Ok(None),
+            }
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(BufReader::with_capacity(1024 * 1024, file))
+}
+
+fn check_version(path: &Path, version: u64) -> Result<()> {
+    if version > FORMAT_VERSION as u64 {
+        bail!(
+            "{} was written by a newer dedup (format {}, this one reads up to {}), please update",
+            path.display(),
+            version,
+            FORMAT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Load all records of `path` into memory.
+pub fn read_all<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let mut records = Vec::new();
+    for_each(path, |record| {
+        records.push(record);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+/// Accepts both a bare array and the `{"format_version", "meta", "records"}`
+/// envelope, streaming the records to `f`.
+struct RecordsVisitor<'a, T, F> {
+    path: &'a Path,
+    f: &'a mut F,
+    error: &'a mut Option<anyhow::Error>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T, F> Visitor<'de> for RecordsVisitor<'_, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array or a dedup result object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(record) = seq.next_element::<T>()? {
+            if let Err(e) = (self.f)(record) {
+                *self.error = Some(e);
+                return Err(de::Error::custom("aborted"));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let RecordsVisitor { path, f, error, .. } = self;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "format_version" => {
+                    if let Err(e) = check_version(path, map.next_value()?) {
+                        *error = Some(e);
+                        return Err(de::Error::custom("aborted"));
+                    }
+                }
+                "records" => map.next_value_seed(RecordsVisitor {
+                    path,
+                    f: &mut *f,
+                    error: &mut *error,
+                    marker: PhantomData::<T>,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de, T, F> DeserializeSeed<'de> for RecordsVisitor<'_, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+/// Reads up to the `meta` entry, then stops the parser with [`STOP`].
+struct MetaVisitor<'a> {
+    path: &'a Path,
+    found: &'a mut Option<Result<Option<ScanMeta>>>,
+}
+
+impl<'de> Visitor<'de> for MetaVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array or a dedup result object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, _seq: A) -> Result<(), A::Error> {
+        *self.found = Some(Ok(None));
+        Err(de::Error::custom(STOP))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "format_version" => {
+                    if let Err(e) = check_version(self.path, map.next_value()?) {
+                        *self.found = Some(Err(e));
+                        return Err(de::Error::custom(STOP));
+                    }
+                }
+                "meta" => {
+                    *self.found = Some(Ok(Some(map.next_value()?)));
+                    return Err(de::Error::custom(STOP));
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Streams records into a file, written atomically on [`RecordWriter::finish`].
+///
+/// JSON output is byte for byte what `serde_json::to_writer_pretty` produces
+/// for the whole envelope (or the bare array without metadata).
+pub struct RecordWriter {
+    file: AtomicFile,
+    format: Format,
+    enveloped: bool,
+    count: u64,
+}
+
+impl RecordWriter {
+    /// Create `path`, the format follows from its extension. `meta` goes into
+    /// the envelope header; without it a bare array is written.
+    pub fn create(path: &Path, meta: Option<&ScanMeta>) -> Result<Self> {
+        let mut file = AtomicFile::create(path)?;
+        let format = Format::from_path(path);
+
+        if let Some(meta) = meta {
+            let header = Header {
+                format_version: FORMAT_VERSION,
+                meta: meta.clone(),
+            };
+            let w = file.writer();
+            match format {
+                Format::Ndjson => {
+                    serde_json::to_writer(&mut *w, &header)?;
+                    w.write_all(b"\n")?;
+                }
+                Format::Json => {
+                    write!(w, "{{\n  \"format_version\": {},\n  \"meta\": ", FORMAT_VERSION)?;
+                    write_indented(w, &header.meta, b"\n  ")?;
+                    w.write_all(b",\n  \"records\": ")?;
+                }
+            }
+        }
+
+        Ok(RecordWriter {
+            file,
+            format,
+            enveloped: meta.is_some(),
+            count: 0,
+        })
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        let w = self.file.writer();
+        match self.format {
+            Format::Ndjson => {
+                serde_json::to_writer(&mut *w, record)?;
+                w.write_all(b"\n")?;
+            }
+            Format::Json => {
+                let newline: &[u8] = if self.enveloped { b"\n    " } else { b"\n  " };
+                w.write_all(if self.count == 0 { b"[" } else { b"," })?;
+                w.write_all(newline)?;
+                write_indented(w, record, newline)?;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Number of records written so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.format == Format::Json {
+            let w = self.file.writer();
+            w.write_all(match (self.count, self.enveloped) {
+                (0, false) => b"[]",
+                (_, false) => b"\n]",
+                (0, true) => b"[]\n}",
+                (_, true) => b"\n  ]\n}",
+            })?;
+        }
+        self.file.commit()
+    }
+}
+
+/// Pretty print `value`, using `newline` (line break plus indentation) for
+/// its nested lines.
+fn write_indented<T: Serialize>(w: &mut impl Write, value: &T, newline: &[u8]) -> Result<()> {
+    let pretty = serde_json::to_vec_pretty(value)?;
+    for (i, line) in pretty.split(|b| *b == b'\n').enumerate() {
+        if i > 0 {
+            w.write_all(newline)?;
+        }
+        w.write_all(line)?;
+    }
+    Ok(())
+}
+
+/// Write all `records` to `path` in the format given by its extension.
+pub fn write_all<'a, T: Serialize + 'a>(
+    path: &Path,
+    meta: Option<&ScanMeta>,
+    records: impl IntoIterator<Item = &'a T>,
+) -> Result<()> {
+    let mut writer = RecordWriter::create(path, meta)?;
+    for record in records {
+        writer.write(record)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::ScanSettings;
+    use crate::types::FileEntry;
+    use std::fs;
+
+    fn meta() -> ScanMeta {
+        ScanMeta::new(vec!["/data".to_string()], ScanSettings::default(), "blake3", crate::meta::now())
+    }
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry {
+            path: path.to_string(),
+            size: 3,
+            checksum: "abc".to_string(),
+            ..FileEntry::default()
+        }
+    }
+
+    #[test]
+    fn reads_legacy_bare_array() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all_files.json");
+        fs::write(&path, r#"[{"path": "/a", "size": 3, "checksum": "abc"}, {"path": "/b", "size": 3, "checksum": "abc"}]"#)
+            .unwrap();
+
+        let files: Vec<FileEntry> = read_all(&path).unwrap();
+        assert_eq!(files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), ["/a", "/b"]);
+        assert!(read_meta(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn round_trips_the_envelope() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["all_files.json", "all_files.ndjson"] {
+            let path = dir.path().join(name);
+            write_all(&path, Some(&meta()), &[entry("/a"), entry("/b")]).unwrap();
+
+            let files: Vec<FileEntry> = read_all(&path).unwrap();
+            assert_eq!(files.len(), 2, "{}", name);
+            assert_eq!(read_meta(&path).unwrap().unwrap().roots, ["/data"], "{}", name);
+        }
+    }
+
+    #[test]
+    fn reads_legacy_ndjson_without_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all_files.ndjson");
+        fs::write(&path, "{\"path\": \"/a\", \"size\": 3, \"checksum\": \"abc\"}\n\n").unwrap();
+
+        let files: Vec<FileEntry> = read_all(&path).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(read_meta(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_newer_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all_files.ndjson");
+        fs::write(&path, "{\"format_version\": 99, \"meta\": {}}\n").unwrap();
+        assert!(read_all::<FileEntry>(&path).is_err());
+
+        let path = dir.path().join("all_files.json");
+        fs::write(&path, r#"{"format_version": 99, "meta": {}, "records": []}"#).unwrap();
+        assert!(read_all::<FileEntry>(&path).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_and_invalid_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all_files.json");
+        fs::write(&path, r#"[{"path": "/a", "size": 3, "checksum": "abc"}, {"path": "/b", "si"#).unwrap();
+        assert!(read_all::<FileEntry>(&path).is_err());
+
+        let path = dir.path().join("all_files.ndjson");
+        fs::write(&path, "{\"path\": \"/a\", \"size\": 3, \"checksum\": \"abc\"}\n{\"path\": \n").unwrap();
+        let error = read_all::<FileEntry>(&path).unwrap_err();
+        assert!(format!("{:#}", error).contains(":2: invalid record"), "{:#}", error);
+    }
+}
@@ -0,0 +1,290 @@
+use anyhow::{bail, Result};
+use base64::Engine;
+use std::{io::Write, path::Path};
+
+use crate::group::GroupStats;
+use crate::media::MediaInfo;
+use crate::output::AtomicFile;
+use crate::similar;
+use crate::types::FileEntry;
+
+/// Thumbnails are scaled to fit into this many pixels.
+const THUMBNAIL_SIZE: u32 = 160;
+
+/// Report types, chosen by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Ok(ReportFormat::Csv),
+            Some("html") | Some("htm") => Ok(ReportFormat::Html),
+            _ => bail!("{}: unknown report type, use .csv or .html", path.display()),
+        }
+    }
+}
+
+/// Write one CSV row per file: group id (1-based), path, size, checksum, the
+/// similarity and hash distance to the first member of similar groups, the
+/// source label of merged inventories and the media metadata of
+/// `scan --media`.
+pub fn write_csv(path: &Path, groups: &[Vec<FileEntry>]) -> Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    let w = file.writer();
+
+    writeln!(
+        w,
+        "group,path,size,checksum,similarity,distance,source,captured_at,width,height,camera,duration,gps"
+    )?;
+    for (index, group) in groups.iter().enumerate() {
+        for entry in group {
+            let media = entry.media.clone().unwrap_or_default();
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                index + 1,
+                csv_field(&entry.path),
+                entry.size,
+                csv_field(&entry.checksum),
+                entry.similarity.map(|s| format!("{:.3}", s)).unwrap_or_default(),
+                entry.distance.map(|d| d.to_string()).unwrap_or_default(),
+                csv_field(entry.source.as_deref().unwrap_or_default()),
+                media.captured_at.map(|t| t.to_string()).unwrap_or_default(),
+                media.width.map(|v| v.to_string()).unwrap_or_default(),
+                media.height.map(|v| v.to_string()).unwrap_or_default(),
+                csv_field(&media.camera().unwrap_or_default()),
+                media.duration.map(|d| format!("{:.1}", d)).unwrap_or_default(),
+                if entry.media.is_some() { media.gps.to_string() } else { String::new() }
+            )?;
+        }
+    }
+
+    file.commit()
+}
+
+/// Write a self-contained HTML page with one collapsible section per group.
+///
+/// With `thumbnails`, the first readable image of each group is scaled down
+/// and embedded, so the page can be mailed or copied around on its own.
+pub fn write_html(path: &Path, groups: &[Vec<FileEntry>], thumbnails: bool) -> Result<()> {
+    let stats = GroupStats::from_groups(groups);
+    let mut file = AtomicFile::create(path)?;
+    let w = file.writer();
+
+    write!(w, "{}", HTML_HEAD)?;
+    writeln!(
+        w,
+        "<p class=\"summary\">{} groups, {} files, <b>{}</b> can be freed by keeping one copy each.</p>",
+        stats.duplicate_groups,
+        stats.duplicate_files,
+        format_size(stats.potential_savings)
+    )?;
+    write!(w, "{}", HTML_CONTROLS)?;
+
+    writeln!(w, "<div id=\"groups\">")?;
+    for (index, group) in groups.iter().enumerate() {
+        let Some(first) = group.first() else {
+            continue;
+        };
+        let wasted = first.size * (group.len() as u64 - 1);
+
+        writeln!(
+            w,
+            "<details class=\"group\" data-wasted=\"{}\" data-size=\"{}\" data-count=\"{}\" data-path=\"{}\">",
+            wasted,
+            first.size,
+            group.len(),
+            escape(&first.path)
+        )?;
+        write!(w, "<summary>")?;
+        if thumbnails {
+            if let Some(data) = group.iter().find_map(|e| thumbnail(&e.path)) {
+                write!(w, "<img class=\"thumb\" alt=\"\" src=\"data:image/jpeg;base64,{}\">", data)?;
+            }
+        }
+        writeln!(
+            w,
+            "<span class=\"id\">#{}</span> {} copies × {} — <b>{} wasted</b> <span class=\"name\">{}</span></summary>",
+            index + 1,
+            group.len(),
+            format_size(first.size),
+            format_size(wasted),
+            escape(file_name(&first.path))
+        )?;
+
+        writeln!(w, "<table><tr><th>Path</th><th>Size</th><th>Media</th><th>Checksum</th></tr>")?;
+        for entry in group {
+            writeln!(
+                w,
+                "<tr><td>{}{}</td><td class=\"num\">{}</td><td class=\"media\">{}</td><td class=\"sum\">{}{}</td></tr>",
+                entry
+                    .source
+                    .as_deref()
+                    .map(|s| format!("<span class=\"source\">{}</span> ", escape(s)))
+                    .unwrap_or_default(),
+                escape(&entry.path),
+                entry.size,
+                escape(&entry.media.as_ref().map(media_summary).unwrap_or_default()),
+                escape(&entry.checksum),
+                similarity_summary(entry)
+            )?;
+        }
+        writeln!(w, "</table>\n</details>")?;
+    }
+    writeln!(w, "</div>")?;
+    write!(w, "{}", HTML_TAIL)?;
+
+    file.commit()
+}
+
+/// `<br>93% similar, 4 bits apart` for members of similar groups.
+fn similarity_summary(entry: &FileEntry) -> String {
+    let Some(similarity) = entry.similarity else {
+        return String::new();
+    };
+    match entry.distance {
+        Some(distance) => format!("<br>{:.0}% similar, {} bits apart", similarity * 100.0, distance),
+        None => format!("<br>{:.0}% similar", similarity * 100.0),
+    }
+}
+
+/// `1536` -> `1.50 KB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+/// `4000×3000 · Canon EOS 5D · 2024-05-01 12:00 · GPS`
+fn media_summary(media: &MediaInfo) -> String {
+    let mut parts = Vec::new();
+    if let (Some(width), Some(height)) = (media.width, media.height) {
+        parts.push(format!("{}×{}", width, height));
+    }
+    if let Some(duration) = media.duration {
+        parts.push(format!("{:.1} s", duration));
+    }
+    parts.extend(media.camera());
+    if let Some(captured) = media.captured_at {
+        parts.push(captured.format("%Y-%m-%d %H:%M").to_string());
+    }
+    if media.gps {
+        parts.push("GPS".to_string());
+    }
+    parts.join(" · ")
+}
+
+/// Small JPEG of a local image file as base64, `None` for anything else.
+fn thumbnail(path: &str) -> Option<String> {
+    if !similar::is_image(path) {
+        return None;
+    }
+
+    let image = image::open(path).ok()?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .ok()?;
+    Some(base64::engine::general_purpose::STANDARD.encode(jpeg))
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Duplicate files</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+.summary { font-size: 1.1em; }
+.controls { margin: 1em 0; }
+.controls select, .controls button { font-size: 1em; margin-right: .5em; }
+details.group { border: 1px solid #ccc; border-radius: 6px; margin: .5em 0; padding: .4em .8em; }
+details.group[open] { background: #fafafa; }
+summary { cursor: pointer; display: flex; align-items: center; gap: .6em; }
+summary .id { color: #888; min-width: 3em; }
+summary .name { color: #555; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+img.thumb { max-width: 80px; max-height: 80px; border-radius: 4px; }
+table { border-collapse: collapse; margin: .6em 0; width: 100%; }
+th, td { text-align: left; padding: .2em .6em; border-bottom: 1px solid #e4e4e4; }
+td { word-break: break-all; }
+td.num { text-align: right; white-space: nowrap; }
+span.source { background: #e8eefc; border-radius: 3px; padding: 0 .3em; font-size: .85em; }
+td.media { white-space: nowrap; color: #555; font-size: .9em; }
+td.sum { font-family: monospace; font-size: .85em; color: #666; }
+</style>
+</head>
+<body>
+<h1>Duplicate files</h1>
+"#;
+
+const HTML_CONTROLS: &str = r#"<div class="controls">
+<label>Sort by
+<select id="sort">
+<option value="wasted">wasted space</option>
+<option value="size">file size</option>
+<option value="count">number of copies</option>
+<option value="path">path</option>
+</select></label>
+<button id="expand">Expand all</button>
+<button id="collapse">Collapse all</button>
+</div>
+"#;
+
+const HTML_TAIL: &str = r#"<script>
+const list = document.getElementById('groups');
+function sortGroups(key) {
+  const items = Array.from(list.children);
+  items.sort((a, b) => key === 'path'
+    ? a.dataset.path.localeCompare(b.dataset.path)
+    : Number(b.dataset[key]) - Number(a.dataset[key]));
+  items.forEach(item => list.appendChild(item));
+}
+document.getElementById('sort').addEventListener('change', e => sortGroups(e.target.value));
+document.getElementById('expand').addEventListener('click', () => list.querySelectorAll('details').forEach(d => d.open = true));
+document.getElementById('collapse').addEventListener('click', () => list.querySelectorAll('details').forEach(d => d.open = false));
+sortGroups('wasted');
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStatus {
+    Deleted,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    url: String,
+    status: ItemStatus,
+}
+
+/// Per-URL progress of a delete run.
+///
+/// Stored as an append-only JSON-lines log next to the input file, one record
+/// per finished request, so an interrupted run loses at most the request in
+/// flight. When the log is replayed the last record for a URL wins.
+pub struct DeleteState {
+    path: PathBuf,
+    items: HashMap<String, ItemStatus>,
+    log: Option<File>,
+}
+
+impl DeleteState {
+    /// State file belonging to `input`, e.g. `duplicates.json.state`.
+    pub fn path_for(input: &Path) -> PathBuf {
+        let mut name = input.file_name().unwrap_or_default().to_os_string();
+        name.push(".state");
+        input.with_file_name(name)
+    }
+
+    /// Load the state for `input`; a missing state file means no progress yet.
+    pub fn load(input: &Path) -> Result<Self> {
+        let path = Self::path_for(input);
+        let mut items = HashMap::new();
+
+        if path.exists() {
+            let file = File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            for (lineno, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // a torn last line from a crash is expected, everything else is not
+                match serde_json::from_str::<Record>(&line) {
+                    Ok(record) => {
+                        items.insert(record.url, record.status);
+                    }
+                    Err(e) => eprintln!(
+                        "Ignoring invalid line {} in {}: {}",
+                        lineno + 1,
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+        }
+
+        Ok(DeleteState {
+            path,
+            items,
+            log: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn status(&self, url: &str) -> Option<ItemStatus> {
+        self.items.get(url).copied()
+    }
+
+    pub fn count(&self, status: ItemStatus) -> usize {
+        self.items.values().filter(|s| **s == status).count()
+    }
+
+    /// Record the outcome for `url` and flush it to disk immediately.
+    pub fn record(&mut self, url: &str, status: ItemStatus) -> Result<()> {
+        if self.log.is_none() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {}", self.path.display()))?;
+            // finish a torn last line, else the first record would be glued to it
+            if file.metadata()?.len() > 0 {
+                let mut last = [0u8];
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+                if last[0] != b'\n' {
+                    file.write_all(b"\n")?;
+                }
+            }
+            self.log = Some(file);
+        }
+        let log = self.log.as_mut().expect("log is open");
+
+        let mut line = serde_json::to_string(&Record {
+            url: url.to_string(),
+            status,
+        })?;
+        line.push('\n');
+        log.write_all(line.as_bytes())?;
+        log.sync_data()?;
+
+        self.items.insert(url.to_string(), status);
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::task;
+use walkdir::WalkDir;
+
+use crate::archive;
+use crate::hash::{HashAlgorithm, MultiHasher};
+use crate::media::MediaInfo;
+use crate::types::FileEntry;
+
+/// Files smaller than this are ignored unless configured otherwise.
+pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Progress report, passed to the callback after every hashed file.
+#[derive(Debug)]
+pub struct Progress<'a> {
+    /// The file that was just hashed.
+    pub path: &'a str,
+    pub size: u64,
+    /// Files hashed so far, including this one.
+    pub files: u64,
+    /// Bytes hashed so far, including this one.
+    pub bytes: u64,
+}
+
+type ProgressFn = dyn Fn(&Progress) + Send + Sync;
+
+/// What to scan and how, create it with [`ScanOptions::builder`].
+#[derive(Clone)]
+pub struct ScanOptions {
+    root: PathBuf,
+    min_size: u64,
+    follow_links: bool,
+    algorithms: Vec<HashAlgorithm>,
+    media: bool,
+    archives: bool,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl ScanOptions {
+    pub fn builder(root: impl Into<PathBuf>) -> ScanOptionsBuilder {
+        ScanOptionsBuilder {
+            options: ScanOptions {
+                root: root.into(),
+                min_size: DEFAULT_MIN_SIZE,
+                follow_links: false,
+                algorithms: vec![HashAlgorithm::Blake3],
+                media: false,
+                archives: false,
+                progress: None,
+            },
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn min_size(&self) -> u64 {
+        self.min_size
+    }
+
+    pub fn algorithms(&self) -> &[HashAlgorithm] {
+        &self.algorithms
+    }
+}
+
+pub struct ScanOptionsBuilder {
+    options: ScanOptions,
+}
+
+impl ScanOptionsBuilder {
+    /// Ignore files smaller than `bytes`, default 1024.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.options.min_size = bytes;
+        self
+    }
+
+    /// Follow symbolic links while walking, default off.
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.options.follow_links = follow;
+        self
+    }
+
+    /// Hash with these algorithms, the first one gives the checksum and the
+    /// others go to `FileEntry::hashes`. Default BLAKE3.
+    pub fn hashes(mut self, algorithms: &[HashAlgorithm]) -> Self {
+        if !algorithms.is_empty() {
+            self.options.algorithms = algorithms.to_vec();
+        }
+        self
+    }
+
+    /// Read capture date, camera, dimensions and duration of images and
+    /// videos into `FileEntry::media`, default off.
+    pub fn media(mut self, media: bool) -> Self {
+        self.options.media = media;
+        self
+    }
+
+    /// Also hash the files inside zip, tar and tar.gz archives as virtual
+    /// entries `archive.zip!/member`, default off. `--min-size` applies to
+    /// the members, `media` does not.
+    pub fn archives(mut self, archives: bool) -> Self {
+        self.options.archives = archives;
+        self
+    }
+
+    /// Called from the worker threads after every hashed file.
+    pub fn on_progress(mut self, f: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.options.progress = Some(Arc::new(f));
+        self
+    }
+
+    pub fn build(self) -> ScanOptions {
+        self.options
+    }
+}
+
+/// A file that could not be hashed.
+#[derive(Debug, Serialize)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanResult {
+    /// Hashed files, sorted by path.
+    pub files: Vec<FileEntry>,
+    pub errors: Vec<ScanError>,
+    /// Number of hashed files.
+    pub total_files: u64,
+    /// Number of hashed bytes.
+    pub total_bytes: u64,
+}
+
+struct Stats {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    min_size: u64,
+    algorithms: Vec<HashAlgorithm>,
+    media: bool,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl Stats {
+    /// Count a hashed file and report progress.
+    fn record(&self, path: &str, size: u64) {
+        let file_count = self.files.fetch_add(1, Ordering::Relaxed) + 1;
+        let byte_count = self.bytes.fetch_add(size, Ordering::Relaxed) + size;
+
+        if let Some(progress) = &self.progress {
+            progress(&Progress {
+                path,
+                size,
+                files: file_count,
+                bytes: byte_count,
+            });
+        }
+    }
+}
+
+/// Hash every file below the root, each file on tokio's blocking pool.
+pub async fn scan(options: &ScanOptions) -> Result<ScanResult> {
+    let stats = Arc::new(Stats {
+        files: AtomicU64::new(0),
+        bytes: AtomicU64::new(0),
+        min_size: options.min_size,
+        algorithms: options.algorithms.clone(),
+        media: options.media,
+        progress: options.progress.clone(),
+    });
+
+    let mut tasks = Vec::new();
+    let mut archive_tasks = Vec::new();
+
+    let walker = WalkDir::new(&options.root).follow_links(options.follow_links);
+    for entry in walker.into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue, // ignore
+            };
+
+            if metadata.len() < options.min_size {
+                continue; // ignore small files
+            }
+
+            let path = entry.path().to_path_buf();
+            if options.archives && archive::is_archive(&path.to_string_lossy()) {
+                let (path, stats) = (path.clone(), stats.clone());
+                archive_tasks.push((
+                    path.clone(),
+                    task::spawn_blocking(move || process_archive(path, stats)),
+                ));
+            }
+            let stats = stats.clone();
+
+            tasks.push((
+                path.clone(),
+                task::spawn_blocking(move || process_file(path, stats)),
+            ));
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for (path, task) in tasks {
+        match task.await {
+            Ok(Ok(entry)) => files.push(entry),
+            Ok(Err(e)) => errors.push(ScanError {
+                path,
+                message: format!("{:#}", e),
+            }),
+            Err(e) => errors.push(ScanError {
+                path,
+                message: format!("Task failed: {}", e),
+            }),
+        }
+    }
+
+    for (path, task) in archive_tasks {
+        match task.await {
+            Ok(Ok(members)) => files.extend(members),
+            Ok(Err(e)) => errors.push(ScanError {
+                path,
+                message: format!("{:#}", e),
+            }),
+            Err(e) => errors.push(ScanError {
+                path,
+                message: format!("Task failed: {}", e),
+            }),
+        }
+    }
+
+    // ---- sort all files by path
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(ScanResult {
+        files,
+        errors,
+        total_files: stats.files.load(Ordering::Relaxed),
+        total_bytes: stats.bytes.load(Ordering::Relaxed),
+    })
+}
+
+fn process_file(path: PathBuf, stats: Arc<Stats>) -> Result<FileEntry> {
+    let mut file = File::open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let metadata = file.metadata()?;
+    let size = metadata.len();
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+
+    let mut hasher = MultiHasher::new(&stats.algorithms);
+    let mut buffer = vec![0u8; 2 * 1024 * 1024];
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    let media = if stats.media { MediaInfo::read(&path) } else { None };
+    let path = path.to_string_lossy().to_string();
+    stats.record(&path, size);
+
+    let mut entry = FileEntry {
+        path,
+        size,
+        mtime,
+        media,
+        ..FileEntry::default()
+    };
+    entry.set_hashes(hasher.finalize());
+    Ok(entry)
+}
+
+/// Hash the members of an archive; a broken archive fails as a whole.
+fn process_archive(path: PathBuf, stats: Arc<Stats>) -> Result<Vec<FileEntry>> {
+    let archive_path = path.to_string_lossy().to_string();
+    let mut buffer = vec![0u8; 2 * 1024 * 1024];
+    let mut entries = Vec::new();
+
+    archive::for_each_member(&path, |member, reader| {
+        if member.size < stats.min_size {
+            return Ok(()); // ignore small files
+        }
+
+        let path = archive::member_path(&archive_path, &member.name);
+        let mut hasher = MultiHasher::new(&stats.algorithms);
+        loop {
+            let n = reader
+                .read(&mut buffer)
+                .with_context(|| format!("Failed to read {}", path))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        stats.record(&path, member.size);
+
+        let mut entry = FileEntry {
+            path,
+            size: member.size,
+            mtime: member.mtime,
+            ..FileEntry::default()
+        };
+        entry.set_hashes(hasher.finalize());
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/*
+ * uses more than 80 gb RAM, takes longer 
+ *
+fn process_file_optimized(path: PathBuf, stats: Arc<Stats>) -> Result<FileEntry> {
+    let file = File::open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let metadata = file.metadata()?;
+    let size = metadata.len();
+
+    // Thresholds
+    const SMALL_FILE: u64 = 1 * 1024 * 1024;   // 1 MB
+    const MEDIUM_FILE: u64 = 128 * 1024 * 1024; // 128 MB
+
+    let hash_hex = if size < SMALL_FILE {
+        // kleine Dateien: 64 KB Buffer
+        let mut hasher = Hasher::new();
+        let mut buffer = [0u8; 64 * 1024];
+        let mut f = file;
+        loop {
+            let n = f.read(&mut buffer)?;
+            if n == 0 { break; }
+            hasher.update(&buffer[..n]);
+        }
+        hasher.finalize().to_hex().to_string()
+    } else if size < MEDIUM_FILE {
+        // mittlere Dateien: 4 MB Buffer
+        let mut hasher = Hasher::new();
+        let mut buffer = vec![0u8; 4 * 1024 * 1024];
+        let mut f = file;
+        loop {
+            let n = f.read(&mut buffer)?;
+            if n == 0 { break; }
+            hasher.update(&buffer[..n]);
+        }
+        hasher.finalize().to_hex().to_string()
+    } else {
+        // große Dateien: mmap
+        let mmap = unsafe { Mmap::map(&file)? };
+        blake3::hash(&mmap).to_hex().to_string()
+    };
+
+    // Statistik
+    let file_count = stats.files.fetch_add(1, Ordering::Relaxed) + 1;
+    let byte_count = stats.bytes.fetch_add(size, Ordering::Relaxed) + size;
+
+    if file_count % 100 == 0 {
+        println!(
+            "Processed {:>8} files ({:.2} MB)",
+            file_count,
+            byte_count as f64 / 1_048_576.0
+        );
+    }
+
+    Ok(FileEntry {
+        path: path.to_string_lossy().to_string(),
+        size,
+        blake3: hash_hex, // optional: kannst auch field umbenennen
+    })
+}
+*/
@@ -0,0 +1,469 @@
+use anyhow::{bail, Context, Result};
+use image::{imageops::FilterType, DynamicImage, ImageDecoder, ImageReader};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tokio::task::JoinSet;
+
+use crate::archive;
+use crate::scan::ScanError;
+use crate::types::FileEntry;
+use crate::video::Fingerprint;
+
+/// Hamming distance up to which two 64-bit hashes count as similar by default.
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// Extensions the image decoder can read.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// Extensions of HEIF images, decoded with a [`HeicConverter`].
+pub const HEIC_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+/// Perceptual hash variants, both 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum PerceptualHash {
+    /// Brightness gradients of a 9x8 thumbnail, fast
+    #[value(name = "dhash")]
+    DHash,
+    /// Low frequencies of a 32x32 DCT, more robust against re-encoding
+    #[value(name = "phash")]
+    PHash,
+}
+
+impl PerceptualHash {
+    pub fn name(self) -> &'static str {
+        match self {
+            PerceptualHash::DHash => "dhash",
+            PerceptualHash::PHash => "phash",
+        }
+    }
+
+    /// Hash the image at `path`, honouring its EXIF orientation.
+    pub fn compute(self, path: &Path) -> Result<u64> {
+        Ok(self.compute_image(&load(path)?))
+    }
+
+    pub fn compute_image(self, image: &DynamicImage) -> u64 {
+        match self {
+            PerceptualHash::DHash => dhash(image),
+            PerceptualHash::PHash => phash(image),
+        }
+    }
+}
+
+/// An external HEIC to JPEG converter, called as `<path> <input> <output.jpg>`
+/// like libheif's `heif-convert`.
+#[derive(Debug, Clone)]
+pub struct HeicConverter {
+    path: PathBuf,
+}
+
+impl HeicConverter {
+    /// Use the binary at `path`, failing early if it can't be started.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        // heif-convert has no reliable --version, starting it is enough
+        Command::new(&path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run {}, is libheif installed?", path.display()))?;
+        Ok(HeicConverter { path })
+    }
+
+    /// Decode the HEIC image at `path` through a temporary JPEG. The
+    /// converter applies the orientation itself.
+    pub fn load(&self, path: &Path) -> Result<DynamicImage> {
+        let jpeg = tempfile::Builder::new()
+            .prefix("dedup-heic-")
+            .suffix(".jpg")
+            .tempfile()
+            .context("Failed to create a temporary file")?;
+        let output = Command::new(&self.path)
+            .arg(path)
+            .arg(jpeg.path())
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {}", self.path.display()))?;
+        if !output.status.success() {
+            bail!(
+                "{}: {} failed: {}",
+                path.display(),
+                self.path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        image::open(jpeg.path()).with_context(|| format!("Failed to decode converted {}", path.display()))
+    }
+}
+
+/// `dhash:<16 hex digits>` as stored in `FileEntry::phash`.
+pub fn format_hash(kind: PerceptualHash, hash: u64) -> String {
+    format!("{}:{:016x}", kind.name(), hash)
+}
+
+/// Inverse of [`format_hash`].
+pub fn parse_hash(value: &str) -> Option<(PerceptualHash, u64)> {
+    let (name, hex) = value.split_once(':')?;
+    let kind = match name {
+        "dhash" => PerceptualHash::DHash,
+        "phash" => PerceptualHash::PHash,
+        _ => return None,
+    };
+    Some((kind, u64::from_str_radix(hex, 16).ok()?))
+}
+
+/// Whether `path` has one of the [`IMAGE_EXTENSIONS`].
+pub fn is_image(path: &str) -> bool {
+    has_extension(path, IMAGE_EXTENSIONS)
+}
+
+/// Whether `path` has one of the [`HEIC_EXTENSIONS`].
+pub fn is_heic(path: &str) -> bool {
+    has_extension(path, HEIC_EXTENSIONS)
+}
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Set `phash` on the local images among `files` that don't have a hash of
+/// this kind yet, decoding a few images at a time. HEIC images are decoded
+/// with `heic` if given. Undecodable images, and HEIC images without a
+/// converter, are returned as errors and keep `phash` unset.
+pub async fn hash_images(
+    files: &mut [FileEntry],
+    kind: PerceptualHash,
+    heic: Option<&HeicConverter>,
+) -> Result<Vec<ScanError>> {
+    let candidates: Vec<usize> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| !f.path.starts_with("http://") && !f.path.starts_with("https://"))
+        .filter(|(_, f)| !archive::is_member(&f.path))
+        .filter(|(_, f)| is_image(&f.path) || is_heic(&f.path))
+        .filter(|(_, f)| !f.phash.as_deref().and_then(parse_hash).is_some_and(|(k, _)| k == kind))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let heic = heic.cloned();
+    hash_parallel(files, candidates, move |path| {
+        let hash = if is_heic(&path.to_string_lossy()) {
+            let Some(converter) = &heic else {
+                bail!("HEIC image skipped, pass --heic-converter to decode it");
+            };
+            kind.compute_image(&converter.load(path)?)
+        } else {
+            kind.compute(path)?
+        };
+        Ok(format_hash(kind, hash))
+    })
+    .await
+}
+
+/// Set `phash` of the `candidates` among `files` to `hash(path)`, a few
+/// files at a time, and return the failures.
+pub(crate) async fn hash_parallel(
+    files: &mut [FileEntry],
+    candidates: Vec<usize>,
+    hash: impl Fn(&Path) -> Result<String> + Clone + Send + 'static,
+) -> Result<Vec<ScanError>> {
+    // decoded photos and videos are large, so only work on one per core
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+    let mut errors = Vec::new();
+    let mut tasks = JoinSet::new();
+    let mut pending = candidates.into_iter();
+    loop {
+        while tasks.len() < workers {
+            let Some(idx) = pending.next() else {
+                break;
+            };
+            let path = files[idx].path.clone();
+            let hash = hash.clone();
+            tasks.spawn_blocking(move || (idx, hash(Path::new(&path))));
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        let (idx, result) = joined.context("Hash task failed")?;
+        match result {
+            Ok(hash) => files[idx].phash = Some(hash),
+            Err(e) => errors.push(ScanError {
+                path: files[idx].path.clone().into(),
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+    Ok(errors)
+}
+
+/// Cluster files whose perceptual hashes of the same kind differ in at most
+/// `max_distance` bits. Clusters are transitive, so members of larger ones
+/// can be further apart. Clusters of byte-identical files only are left to
+/// the duplicate groups.
+///
+/// Members are sorted by path, clusters by size, largest first. Each
+/// member's `similarity` and `distance` are set relative to the first one.
+pub fn cluster(files: &[FileEntry], max_distance: u32) -> Vec<Vec<FileEntry>> {
+    let hashed: Vec<(usize, PerceptualHash, u64)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, f)| {
+            let (kind, hash) = parse_hash(f.phash.as_deref()?)?;
+            Some((idx, kind, hash))
+        })
+        .collect();
+
+    let mut sets = DisjointSets::new(files.len());
+    for kind in [PerceptualHash::DHash, PerceptualHash::PHash] {
+        let mut tree = BkTree::default();
+        let mut near = Vec::new();
+        for &(idx, _, hash) in hashed.iter().filter(|(_, k, _)| *k == kind) {
+            near.clear();
+            tree.find(hash, max_distance, &mut near);
+            for &other in &near {
+                sets.union(idx, other);
+            }
+            tree.insert(hash, idx);
+        }
+    }
+
+    let mut clusters = collect_clusters(files, &mut sets, hashed.iter().map(|(idx, _, _)| *idx), |a, b| {
+        let ((_, a), (_, b)) = (parse_hash(a)?, parse_hash(b)?);
+        Some(1.0 - (a ^ b).count_ones() as f64 / 64.0)
+    });
+    for group in &mut clusters {
+        let first = group[0].phash.as_deref().and_then(parse_hash);
+        for entry in group.iter_mut() {
+            entry.distance = match (first, entry.phash.as_deref().and_then(parse_hash)) {
+                (Some((_, a)), Some((_, b))) => Some((a ^ b).count_ones()),
+                _ => None,
+            };
+        }
+    }
+    clusters
+}
+
+/// Whether `group` is a similar group rather than byte-identical copies.
+/// The cleaners other than `remote-clean --policy best-quality` refuse those.
+pub fn is_similar_group(group: &[FileEntry]) -> bool {
+    group.iter().any(|f| {
+        f.similarity.is_some() || f.size != group[0].size || f.checksum != group[0].checksum
+    })
+}
+
+/// Whether the perceptual hashes or video fingerprints `a` and `b` are alike
+/// enough to be joined directly by [`cluster`] or [`video::cluster`], not only
+/// through other members of a cluster.
+///
+/// [`video::cluster`]: crate::video::cluster
+pub fn is_close(a: &str, b: &str, max_distance: u32, min_similarity: f64) -> bool {
+    if let (Some((kind_a, a)), Some((kind_b, b))) = (parse_hash(a), parse_hash(b)) {
+        return kind_a == kind_b && (a ^ b).count_ones() <= max_distance;
+    }
+    match (Fingerprint::parse(a), Fingerprint::parse(b)) {
+        (Some(a), Some(b)) => a.similarity(&b).is_some_and(|s| s >= min_similarity),
+        _ => false,
+    }
+}
+
+/// Clusters of the `members` joined in `sets`, without single files and
+/// clusters of byte-identical files. Each member's `similarity` is
+/// `score(first phash, its phash)`.
+pub(crate) fn collect_clusters(
+    files: &[FileEntry],
+    sets: &mut DisjointSets,
+    members: impl Iterator<Item = usize>,
+    score: impl Fn(&str, &str) -> Option<f64>,
+) -> Vec<Vec<FileEntry>> {
+    let mut clusters: Vec<Vec<usize>> = vec![Vec::new(); files.len()];
+    for idx in members {
+        let root = sets.find(idx);
+        clusters[root].push(idx);
+    }
+
+    let mut similar: Vec<Vec<FileEntry>> = clusters
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .filter(|members| {
+            let keys: BTreeSet<(u64, &str)> = members
+                .iter()
+                .map(|&i| (files[i].size, files[i].checksum.as_str()))
+                .collect();
+            keys.len() > 1
+        })
+        .map(|members| {
+            let mut group: Vec<FileEntry> = members.into_iter().map(|i| files[i].clone()).collect();
+            group.sort_by(|a, b| a.path.cmp(&b.path));
+            let first = group[0].phash.clone().unwrap_or_default();
+            for entry in &mut group {
+                entry.similarity = entry.phash.as_deref().and_then(|hash| score(&first, hash));
+            }
+            group
+        })
+        .collect();
+    similar.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].path.cmp(&b[0].path)));
+    similar
+}
+
+fn load(path: &Path) -> Result<DynamicImage> {
+    let mut decoder = ImageReader::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .with_guessed_format()?
+        .into_decoder()
+        .with_context(|| format!("Failed to decode {}", path.display()))?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .with_context(|| format!("Failed to decode {}", path.display()))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// One bit per pixel pair of a 9x8 grey thumbnail: is the left one brighter?
+fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    dhash_bits(small.as_raw())
+}
+
+/// [`dhash`] of a 9x8 grey image given as row-major bytes.
+pub(crate) fn dhash_bits(pixels: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for row in pixels.chunks_exact(9).take(8) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    hash
+}
+
+/// One bit per coefficient of the 8x8 lowest frequencies of a 32x32 DCT:
+/// is it above their median (without the DC term)?
+fn phash(image: &DynamicImage) -> u64 {
+    const N: usize = 32;
+    let small = image
+        .resize_exact(N as u32, N as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    let cos: Vec<f64> = (0..8 * N)
+        .map(|i| {
+            let (u, x) = (i / N, i % N);
+            (std::f64::consts::PI * (2 * x + 1) as f64 * u as f64 / (2 * N) as f64).cos()
+        })
+        .collect();
+
+    let mut coefficients = [0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0.0;
+            for y in 0..N {
+                for x in 0..N {
+                    sum += pixels[y * N + x] * cos[u * N + x] * cos[v * N + y];
+                }
+            }
+            coefficients[v * 8 + u] = sum;
+        }
+    }
+
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | u64::from(c > median))
+}
+
+/// Burkhard-Keller tree for Hamming distance queries.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    item: usize,
+    /// `(distance to this node, child index)`
+    children: Vec<(u32, usize)>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        let new = self.nodes.len();
+        self.nodes.push(BkNode {
+            hash,
+            item,
+            children: Vec::new(),
+        });
+        if new == 0 {
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let distance = (self.nodes[current].hash ^ hash).count_ones();
+            match self.nodes[current].children.iter().find(|(d, _)| *d == distance) {
+                Some(&(_, child)) => current = child,
+                None => {
+                    self.nodes[current].children.push((distance, new));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Items within `max_distance` of `hash`.
+    fn find(&self, hash: u64, max_distance: u32, out: &mut Vec<usize>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(current) = stack.pop() {
+            let node = &self.nodes[current];
+            let distance = (node.hash ^ hash).count_ones();
+            if distance <= max_distance {
+                out.push(node.item);
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| d.abs_diff(distance) <= max_distance)
+                    .map(|&(_, child)| child),
+            );
+        }
+    }
+}
+
+pub(crate) struct DisjointSets {
+    parent: Vec<usize>,
+}
+
+impl DisjointSets {
+    pub(crate) fn new(len: usize) -> Self {
+        DisjointSets {
+            parent: (0..len).collect(),
+        }
+    }
+
+    pub(crate) fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::group::GroupStats;
+use crate::meta::{self, ScanMeta, ScanSettings};
+use crate::types::FileEntry;
+
+/// SQLite result store, an alternative to `all_files.json` + `duplicates.json`.
+///
+/// * `runs`: one row per scan with its root, host, start/end time and totals
+/// * `files`: one row per file, keyed by path; `dir` is the parent directory
+///   and `group_id` points to the duplicate group if there is one; `hashes`
+///   holds the further hashes of `scan --hash` and `media` the metadata of
+///   `scan --media`, both as JSON objects
+/// * `groups`: one row per `(size, checksum)` with more than one file
+///
+/// `files` is indexed on checksum, size and dir; the path primary key serves
+/// prefix queries like `path >= 'photos/' AND path < 'photos0'`.
+pub struct Store {
+    conn: Connection,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id                INTEGER PRIMARY KEY,
+    root              TEXT NOT NULL,
+    started_at        TEXT NOT NULL,
+    finished_at       TEXT,
+    hostname          TEXT,
+    total_files       INTEGER,
+    total_bytes       INTEGER,
+    duplicate_groups  INTEGER,
+    duplicate_files   INTEGER,
+    potential_savings INTEGER
+);
+CREATE TABLE IF NOT EXISTS groups (
+    id       INTEGER PRIMARY KEY,
+    size     INTEGER NOT NULL,
+    checksum TEXT NOT NULL,
+    count    INTEGER NOT NULL,
+    wasted   INTEGER NOT NULL,
+    UNIQUE (size, checksum)
+);
+CREATE TABLE IF NOT EXISTS files (
+    path     TEXT PRIMARY KEY,
+    dir      TEXT NOT NULL,
+    size     INTEGER NOT NULL,
+    checksum TEXT NOT NULL,
+    mtime    INTEGER,
+    algorithm TEXT,
+    hashes   TEXT,
+    media    TEXT,
+    run_id   INTEGER NOT NULL REFERENCES runs(id),
+    group_id INTEGER REFERENCES groups(id)
+);
+CREATE INDEX IF NOT EXISTS idx_files_checksum ON files(checksum);
+CREATE INDEX IF NOT EXISTS idx_files_size ON files(size);
+CREATE INDEX IF NOT EXISTS idx_files_dir ON files(dir);
+CREATE INDEX IF NOT EXISTS idx_files_group ON files(group_id);
+";
+
+/// Files with one of these extensions are treated as result databases.
+pub fn is_database(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("sqlite") | Some("sqlite3") | Some("db")
+    )
+}
+
+impl Store {
+    /// Open or create the database at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to set up {}", path.display()))?;
+        let store = Store { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Add columns introduced after the first release of the schema.
+    fn migrate(&self) -> Result<()> {
+        for (table, column, kind) in [
+            ("files", "mtime", "INTEGER"),
+            ("files", "algorithm", "TEXT"),
+            ("files", "hashes", "TEXT"),
+            ("files", "media", "TEXT"),
+            ("runs", "hostname", "TEXT"),
+        ] {
+            let exists = self
+                .conn
+                .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+                .exists([table, column])?;
+            if !exists {
+                self.conn
+                    .execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, kind), [])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Open an existing database, e.g. as input of the cleaners.
+    pub fn open_existing(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            bail!("{} does not exist", path.display());
+        }
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+        let store = Store { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Start a new run for `root` and return its id.
+    pub fn begin_run(&self, root: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (root, started_at, hostname) VALUES (?1, ?2, ?3)",
+            params![root, chrono::Local::now().to_rfc3339(), meta::local_hostname()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// The latest finished run as scan metadata, so it can be checked
+    /// like the header of a result file. Runs recorded before the host was
+    /// stored report the host as `unknown`.
+    pub fn last_run(&self) -> Result<Option<ScanMeta>> {
+        // ids only grow, local times with offsets don't sort across DST changes
+        let row = self
+            .conn
+            .query_row(
+                "SELECT id, root, started_at, finished_at, hostname FROM runs
+                 WHERE finished_at IS NOT NULL ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((id, root, started_at, finished_at, hostname)) = row else {
+            return Ok(None);
+        };
+        let parse = |time: &str| {
+            DateTime::parse_from_rfc3339(time).with_context(|| format!("Invalid time {:?} in run {}", time, id))
+        };
+        Ok(Some(ScanMeta {
+            run_id: id.to_string(),
+            roots: vec![root],
+            options: ScanSettings::default(),
+            hash_algorithm: String::new(),
+            started_at: parse(&started_at)?,
+            finished_at: parse(&finished_at)?,
+            hostname: hostname.unwrap_or_else(|| "unknown".to_string()),
+            tool_version: String::new(),
+        }))
+    }
+
+    /// Insert or update `files`, marking them as seen in `run_id`.
+    pub fn upsert_files(&mut self, run_id: i64, files: &[FileEntry]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO files (path, dir, size, checksum, mtime, algorithm, hashes, media, run_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT(path) DO UPDATE SET
+                     size = excluded.size, checksum = excluded.checksum, mtime = excluded.mtime,
+                     algorithm = excluded.algorithm, hashes = excluded.hashes, media = excluded.media,
+                     run_id = excluded.run_id",
+            )?;
+            for f in files {
+                let dir = f.path.rsplit_once('/').map_or("", |(dir, _)| dir);
+                let hashes = if f.hashes.is_empty() {
+                    None
+                } else {
+                    Some(serde_json::to_string(&f.hashes)?)
+                };
+                let media = f.media.as_ref().map(serde_json::to_string).transpose()?;
+                stmt.execute(params![
+                    f.path,
+                    dir,
+                    f.size as i64,
+                    f.checksum,
+                    f.mtime,
+                    f.algorithm,
+                    hashes,
+                    media,
+                    run_id
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Finish `run_id`: drop files below `root` that were not seen in this
+    /// run, recompute the duplicate groups and store the totals.
+    ///
+    /// Below means the path is `root` or starts with `root/`. A rescan of
+    /// `/data` thus also drops rows of an earlier `/data/sub` run, which it
+    /// walked again, while the rows of `/data2` are another tree and stay.
+    pub fn finish_run(&mut self, run_id: i64, root: &str) -> Result<GroupStats> {
+        let root = root.trim_end_matches('/');
+        let dir_prefix = format!("{}/", root);
+        let tx = self.conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM files WHERE run_id <> ?1
+             AND (path = ?2 OR substr(path, 1, length(?3)) = ?3)",
+            params![run_id, root, dir_prefix],
+        )?;
+
+        // ---- regroup
+        tx.execute("UPDATE files SET group_id = NULL WHERE group_id IS NOT NULL", [])?;
+        tx.execute("DELETE FROM groups", [])?;
+        tx.execute(
+            "INSERT INTO groups (size, checksum, count, wasted)
+             SELECT size, checksum, COUNT(*), size * (COUNT(*) - 1)
+             FROM files GROUP BY size, checksum HAVING COUNT(*) > 1",
+            [],
+        )?;
+        tx.execute(
+            "UPDATE files SET group_id = g.id FROM groups g
+             WHERE g.size = files.size AND g.checksum = files.checksum",
+            [],
+        )?;
+
+        let stats = tx.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(count), 0), COALESCE(SUM(wasted), 0) FROM groups",
+            [],
+            |row| {
+                Ok(GroupStats {
+                    duplicate_groups: row.get::<_, i64>(0)? as u64,
+                    duplicate_files: row.get::<_, i64>(1)? as u64,
+                    potential_savings: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )?;
+
+        tx.execute(
+            "UPDATE runs SET finished_at = ?2,
+                 total_files = (SELECT COUNT(*) FROM files WHERE run_id = ?1),
+                 total_bytes = (SELECT COALESCE(SUM(size), 0) FROM files WHERE run_id = ?1),
+                 duplicate_groups = ?3, duplicate_files = ?4, potential_savings = ?5
+             WHERE id = ?1",
+            params![
+                run_id,
+                chrono::Local::now().to_rfc3339(),
+                stats.duplicate_groups as i64,
+                stats.duplicate_files as i64,
+                stats.potential_savings as i64
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(stats)
+    }
+
+    /// Call `f` for every duplicate group, largest files first.
+    pub fn for_each_group(&self, mut f: impl FnMut(Vec<FileEntry>) -> Result<()>) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.group_id, f.path, f.size, f.checksum, f.mtime, f.algorithm, f.hashes, f.media
+             FROM files f JOIN groups g ON g.id = f.group_id
+             ORDER BY g.size DESC, g.id, f.path",
+        )?;
+        let mut rows = stmt.query([])?;
+
+        let mut current: Option<i64> = None;
+        let mut group = Vec::new();
+        while let Some(row) = rows.next()? {
+            let group_id: i64 = row.get(0)?;
+            if current != Some(group_id) && !group.is_empty() {
+                f(std::mem::take(&mut group))?;
+            }
+            current = Some(group_id);
+            group.push(file_entry(row, 1)?);
+        }
+        if !group.is_empty() {
+            f(group)?;
+        }
+        Ok(())
+    }
+
+    /// Call `f` for every file, ordered by path.
+    pub fn for_each_file(&self, mut f: impl FnMut(FileEntry) -> Result<()>) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, size, checksum, mtime, algorithm, hashes, media FROM files ORDER BY path")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            f(file_entry(row, 0)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry from the columns path, size, checksum, mtime, algorithm, hashes and
+/// media starting at `first`.
+fn file_entry(row: &Row, first: usize) -> Result<FileEntry> {
+    let hashes: Option<String> = row.get(first + 5)?;
+    let hashes: BTreeMap<String, String> = match hashes {
+        Some(json) => serde_json::from_str(&json).context("Invalid hashes column")?,
+        None => BTreeMap::new(),
+    };
+    let media: Option<String> = row.get(first + 6)?;
+    let media = media
+        .map(|json| serde_json::from_str(&json))
+        .transpose()
+        .context("Invalid media column")?;
+    Ok(FileEntry {
+        path: row.get(first)?,
+        size: row.get::<_, i64>(first + 1)? as u64,
+        checksum: row.get(first + 2)?,
+        algorithm: row.get(first + 4)?,
+        hashes,
+        media,
+        mtime: row.get(first + 3)?,
+        ..FileEntry::default()
+    })
+}
@@ -1,9 +1,67 @@
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::media::MediaInfo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FileEntry {
     pub path: String,
     pub size: u64,
+    /// Hex hash used for grouping; prefixed with `<algorithm>:` for anything
+    /// but BLAKE3, e.g. `sha1:…`.
     pub checksum: String,
+    /// Algorithm of `checksum`. Missing in files written before `--hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// Further hashes of the file as plain hex, keyed by algorithm.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hashes: BTreeMap<String, String>,
+    /// Perceptual hash of images, `dhash:<hex>` or `phash:<hex>`, or
+    /// frame fingerprint of videos, `vdhash:…`; see `scan --similar`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phash: Option<String>,
+    /// Similarity to the first member of a similar group, from 0 to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    /// Bits the perceptual hash differs from the first member's, for images
+    /// of a similar group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<u32>,
+    /// Capture date, camera and dimensions of images and videos, see
+    /// `scan --media`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaInfo>,
+    /// Modification time in seconds since the Unix epoch, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<i64>,
+    /// Host or source label of merged inventories, see `dedup merge`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
+impl FileEntry {
+    /// Set `checksum` and `algorithm` from the first of `hashes`, the others
+    /// go to `hashes`.
+    pub fn set_hashes(&mut self, hashes: Vec<(String, String)>) {
+        let mut hashes = hashes.into_iter();
+        if let Some((algorithm, hex)) = hashes.next() {
+            self.checksum = if algorithm == "blake3" {
+                hex
+            } else {
+                format!("{}:{}", algorithm, hex)
+            };
+            self.algorithm = Some(algorithm);
+        }
+        self.hashes = hashes.collect();
+    }
+
+    /// Plain hex hash of the file by `algorithm`, if it has one.
+    pub fn hash(&self, algorithm: &str) -> Option<&str> {
+        let primary = match self.checksum.split_once(':') {
+            Some((tag, hex)) => (tag == algorithm).then_some(hex),
+            None => (self.algorithm.as_deref().unwrap_or("blake3") == algorithm)
+                .then_some(self.checksum.as_str()),
+        };
+        primary.or_else(|| self.hashes.get(algorithm).map(String::as_str))
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::archive;
+use crate::scan::ScanError;
+use crate::similar::{self, DisjointSets};
+use crate::types::FileEntry;
+
+/// Extensions handed to ffmpeg.
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "3gp", "mkv", "avi", "webm", "wmv", "mts"];
+
+/// Frames sampled per video at fixed offsets evenly spread over its length.
+/// ffmpeg seeks to each offset exactly instead of taking keyframes, which a
+/// re-encode places elsewhere; trimmed clips shift the offsets and don't match.
+const FRAMES: usize = 16;
+
+/// Similarity from which two videos count as the same clip by default.
+pub const DEFAULT_MIN_SIMILARITY: f64 = 0.9;
+
+/// Videos are only compared if their lengths differ by at most this many
+/// seconds or this fraction of the longer one, whichever is more.
+const DURATION_TOLERANCE: (f64, f64) = (1.0, 0.02);
+
+/// The dHash of one frame per sampled position plus the clip length, stored
+/// as `vdhash:<seconds>:<hex>…` in `FileEntry::phash`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub duration: f64,
+    pub frames: Vec<u64>,
+}
+
+impl Fingerprint {
+    pub fn parse(value: &str) -> Option<Fingerprint> {
+        let mut parts = value.split(':');
+        if parts.next()? != "vdhash" {
+            return None;
+        }
+        let duration = parts.next()?.parse().ok()?;
+        let frames = parts
+            .next()?
+            .as_bytes()
+            .chunks(16)
+            .map(|hex| u64::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Fingerprint { duration, frames })
+    }
+
+    /// Mean share of equal bits over the frames, `None` if the lengths are
+    /// too different. Frames that are blank in both clips don't count.
+    pub fn similarity(&self, other: &Fingerprint) -> Option<f64> {
+        let (absolute, relative) = DURATION_TOLERANCE;
+        let tolerance = absolute.max(relative * self.duration.max(other.duration));
+        if (self.duration - other.duration).abs() > tolerance {
+            return None;
+        }
+
+        let scores: Vec<f64> = self
+            .frames
+            .iter()
+            .zip(&other.frames)
+            .filter(|(a, b)| **a != 0 || **b != 0)
+            .map(|(a, b)| 1.0 - (a ^ b).count_ones() as f64 / 64.0)
+            .collect();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "vdhash:{:.3}:", self.duration)?;
+        for frame in &self.frames {
+            write!(f, "{:016x}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// A local `ffmpeg` binary, used to seek to and scale down single frames.
+#[derive(Debug, Clone)]
+pub struct Ffmpeg {
+    path: PathBuf,
+}
+
+impl Ffmpeg {
+    /// Use the binary at `path`, failing early if it does not run.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let status = Command::new(&path)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run {}, is ffmpeg installed?", path.display()))?;
+        if !status.success() {
+            bail!("{} -version failed", path.display());
+        }
+        Ok(Ffmpeg { path })
+    }
+
+    pub fn fingerprint(&self, video: &Path) -> Result<Fingerprint> {
+        let duration = self.duration(video)?;
+        let frames = (0..FRAMES)
+            .map(|i| {
+                let at = duration * (i as f64 + 0.5) / FRAMES as f64;
+                self.frame(video, at).map(|pixels| similar::dhash_bits(&pixels))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+        Ok(Fingerprint { duration, frames })
+    }
+
+    /// Length in seconds, from the `Duration: 00:01:02.50` line of `ffmpeg -i`.
+    fn duration(&self, video: &Path) -> Result<f64> {
+        // without an output ffmpeg exits with an error, the info is on stderr
+        let output = Command::new(&self.path)
+            .args(["-hide_banner", "-i"])
+            .arg(video)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {}", self.path.display()))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        let value = stderr
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Duration: "))
+            .and_then(|rest| rest.split(',').next())
+            .with_context(|| format!("{}: no duration, not a video?", video.display()))?;
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            let part: f64 = part
+                .parse()
+                .with_context(|| format!("{}: invalid duration {}", video.display(), value))?;
+            seconds = seconds * 60.0 + part;
+        }
+        Ok(seconds)
+    }
+
+    /// The frame at `at` seconds as 9x8 grey pixels.
+    fn frame(&self, video: &Path, at: f64) -> Result<Vec<u8>> {
+        let output = Command::new(&self.path)
+            .args(["-v", "error", "-ss", &format!("{:.3}", at), "-i"])
+            .arg(video)
+            .args(["-frames:v", "1", "-vf", "scale=9:8,format=gray", "-f", "rawvideo", "-"])
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {}", self.path.display()))?;
+        if !output.status.success() || output.stdout.len() < 72 {
+            bail!(
+                "{}: no frame at {:.1}s: {}",
+                video.display(),
+                at,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Whether `path` has one of the [`VIDEO_EXTENSIONS`].
+pub fn is_video(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Set a frame fingerprint as `phash` on the local videos among `files`
+/// that don't have one yet. Videos ffmpeg can't read are returned as errors.
+pub async fn fingerprint_videos(files: &mut [FileEntry], ffmpeg: &Ffmpeg) -> Result<Vec<ScanError>> {
+    let candidates: Vec<usize> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| !f.path.starts_with("http://") && !f.path.starts_with("https://"))
+        .filter(|(_, f)| !archive::is_member(&f.path))
+        .filter(|(_, f)| is_video(&f.path))
+        .filter(|(_, f)| f.phash.as_deref().and_then(Fingerprint::parse).is_none())
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let ffmpeg = ffmpeg.clone();
+    similar::hash_parallel(files, candidates, move |path| Ok(ffmpeg.fingerprint(path)?.to_string())).await
+}
+
+/// Cluster videos whose fingerprints are at least `min_similarity` alike,
+/// like [`similar::cluster`] does for images. Each member's `similarity` is
+/// set relative to the first one.
+pub fn cluster(files: &[FileEntry], min_similarity: f64) -> Vec<Vec<FileEntry>> {
+    let fingerprinted: Vec<(usize, Fingerprint)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, f)| Some((idx, Fingerprint::parse(f.phash.as_deref()?)?)))
+        .collect();
+
+    // videos are few compared to images, comparing all pairs is fine
+    let mut sets = DisjointSets::new(files.len());
+    for (i, (a, fa)) in fingerprinted.iter().enumerate() {
+        for (b, fb) in &fingerprinted[i + 1..] {
+            if fa.similarity(fb).is_some_and(|s| s >= min_similarity) {
+                sets.union(*a, *b);
+            }
+        }
+    }
+
+    similar::collect_clusters(files, &mut sets, fingerprinted.iter().map(|(idx, _)| *idx), |a, b| {
+        Fingerprint::parse(a)?.similarity(&Fingerprint::parse(b)?)
+    })
+}
@@ -0,0 +1,467 @@
+use anyhow::{bail, Context, Result};
+use reqwest::{blocking::Client, Method, Url};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Read,
+};
+
+use crate::credentials::Credentials;
+use crate::hash::{HashAlgorithm, MultiHasher};
+use crate::types::FileEntry;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+    <oc:checksums/>
+  </d:prop>
+</d:propfind>"#;
+
+const TRASHBIN_PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns" xmlns:nc="http://nextcloud.org/ns">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <oc:size/>
+    <nc:trashbin-original-location/>
+    <nc:trashbin-deletion-time/>
+  </d:prop>
+</d:propfind>"#;
+
+const DAV_NS: &str = "DAV:";
+const OC_NS: &str = "http://owncloud.org/ns";
+const NC_NS: &str = "http://nextcloud.org/ns";
+
+/// Server checksum algorithms we fall back to, in order of preference.
+const CHECKSUM_PREFERENCE: [HashAlgorithm; 2] = [HashAlgorithm::Sha1, HashAlgorithm::Md5];
+
+/// How to obtain a checksum for remote files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteHashing {
+    /// Only use server-provided checksums, skip files without one.
+    ServerOnly,
+    /// Use server checksums, download and hash files without one.
+    DownloadMissing,
+    /// Ignore server checksums and download every file.
+    DownloadAll,
+}
+
+impl RemoteHashing {
+    pub fn name(self) -> &'static str {
+        match self {
+            RemoteHashing::ServerOnly => "server-only",
+            RemoteHashing::DownloadMissing => "download-missing",
+            RemoteHashing::DownloadAll => "download-all",
+        }
+    }
+
+    /// Checksums the result contains when downloads are hashed with
+    /// `download`: `server` stands for the `sha1:`/`md5:` values Nextcloud
+    /// reports.
+    pub fn hash_algorithm(self, download: HashAlgorithm) -> String {
+        match self {
+            RemoteHashing::ServerOnly => "server".to_string(),
+            RemoteHashing::DownloadMissing => shared_algorithm(download).name().to_string(),
+            RemoteHashing::DownloadAll => download.name().to_string(),
+        }
+    }
+}
+
+/// The checksum server-hashed and downloaded files are both grouped by with
+/// [`RemoteHashing::DownloadMissing`]: `requested` if Nextcloud can report
+/// it, else SHA-1. Otherwise identical files would never meet in a group.
+pub fn shared_algorithm(requested: HashAlgorithm) -> HashAlgorithm {
+    if CHECKSUM_PREFERENCE.contains(&requested) {
+        requested
+    } else {
+        HashAlgorithm::Sha1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteScanOptions {
+    /// Files smaller than this are ignored.
+    pub min_size: u64,
+    pub hashing: RemoteHashing,
+    /// Algorithms for downloaded files, the first one is also the preferred
+    /// server checksum.
+    pub algorithms: Vec<HashAlgorithm>,
+}
+
+/// One item of a PROPFIND multistatus response.
+#[derive(Debug)]
+struct DavItem {
+    url: Url,
+    is_dir: bool,
+    size: u64,
+    /// `getlastmodified` as Unix time.
+    mtime: Option<i64>,
+    checksums: String,
+}
+
+/// Walk a WebDAV tree starting at `root` with `Depth: 1` PROPFIND requests.
+///
+/// Files with a server checksum (Nextcloud `oc:checksums`) use the first
+/// requested algorithm the server reports, else SHA-1 or MD5, and keep the
+/// other reported ones in `hashes`. Downloaded files are hashed like a local
+/// scan. With [`RemoteHashing::DownloadMissing`] both use the
+/// [`shared_algorithm`] as checksum, files whose server checksums lack it are
+/// downloaded too. `on_file` is called for every produced entry.
+pub fn scan(
+    client: &Client,
+    root: &str,
+    creds: &Credentials,
+    options: &RemoteScanOptions,
+    mut on_file: impl FnMut(&FileEntry),
+) -> Result<Vec<FileEntry>> {
+    let mut root = Url::parse(root).with_context(|| format!("Invalid URL {}", root))?;
+    if !root.path().ends_with('/') {
+        root.set_path(&format!("{}/", root.path()));
+    }
+
+    let primary = options.algorithms.first().copied().unwrap_or(HashAlgorithm::Blake3);
+    let shared = shared_algorithm(primary);
+    let download_algorithms: Vec<HashAlgorithm> = match options.hashing {
+        RemoteHashing::DownloadMissing => std::iter::once(shared).chain(options.algorithms.iter().copied()).collect(),
+        _ => options.algorithms.clone(),
+    };
+
+    let mut files = Vec::new();
+    let mut skipped = 0u64;
+    let mut visited = HashSet::from([dir_key(&root)]);
+    let mut queue = VecDeque::from([root]);
+
+    while let Some(dir) = queue.pop_front() {
+        let items = match propfind(client, &dir, creds) {
+            Ok(items) => items,
+            Err(e) => {
+                eprintln!("Error listing {}: {:#}", dir, e);
+                continue;
+            }
+        };
+
+        for item in items {
+            if is_same_dir(&item.url, &dir) {
+                continue; // the directory itself
+            }
+            if item.is_dir {
+                if visited.insert(dir_key(&item.url)) {
+                    queue.push_back(item.url);
+                }
+                continue;
+            }
+            if item.size < options.min_size {
+                continue;
+            }
+
+            let server_checksums = match options.hashing {
+                RemoteHashing::ServerOnly => server_checksums(&item.checksums, &options.algorithms),
+                RemoteHashing::DownloadMissing => {
+                    let checksums = server_checksums(&item.checksums, &[shared]);
+                    if checksums.first().is_some_and(|(a, _)| a == shared.name()) {
+                        checksums
+                    } else {
+                        Vec::new()
+                    }
+                }
+                RemoteHashing::DownloadAll => Vec::new(),
+            };
+
+            let hashes = if !server_checksums.is_empty() {
+                server_checksums
+            } else if options.hashing == RemoteHashing::ServerOnly {
+                skipped += 1;
+                continue;
+            } else {
+                match download_hash(client, &item.url, creds, &download_algorithms) {
+                    Ok(h) => h,
+                    Err(e) => {
+                        eprintln!("Error processing file: {:#}", e);
+                        continue;
+                    }
+                }
+            };
+
+            let mut entry = FileEntry {
+                path: display_url(&item.url),
+                size: item.size,
+                mtime: item.mtime,
+                ..FileEntry::default()
+            };
+            entry.set_hashes(hashes);
+            on_file(&entry);
+            files.push(entry);
+        }
+    }
+
+    if skipped > 0 {
+        eprintln!(
+            "Skipped {} file(s) without server checksum, use --download to hash them",
+            skipped
+        );
+    }
+
+    Ok(files)
+}
+
+fn propfind(client: &Client, dir: &Url, creds: &Credentials) -> Result<Vec<DavItem>> {
+    let text = propfind_raw(client, dir, creds, PROPFIND_BODY)?;
+    parse_multistatus(dir, &text)
+}
+
+fn propfind_raw(client: &Client, url: &Url, creds: &Credentials, body: &'static str) -> Result<String> {
+    let resp = client
+        .request(Method::from_bytes(b"PROPFIND")?, url.clone())
+        .basic_auth(&creds.user, Some(&creds.password))
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(body)
+        .send()?;
+
+    let status = resp.status();
+    if status.as_u16() != 207 {
+        bail!("HTTP {}", status);
+    }
+
+    Ok(resp.text()?)
+}
+
+fn parse_multistatus(base: &Url, xml: &str) -> Result<Vec<DavItem>> {
+    let doc = roxmltree::Document::parse(xml).context("Invalid PROPFIND response")?;
+
+    let mut items = Vec::new();
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name((DAV_NS, "response")))
+    {
+        let Some(href) = find_text(response, DAV_NS, "href") else {
+            continue;
+        };
+        let url = base
+            .join(href)
+            .with_context(|| format!("Invalid href {}", href))?;
+
+        let is_dir = response
+            .descendants()
+            .any(|n| n.has_tag_name((DAV_NS, "collection")));
+        let size = find_text(response, DAV_NS, "getcontentlength")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+        let mtime = find_text(response, DAV_NS, "getlastmodified")
+            .and_then(|s| chrono::DateTime::parse_from_rfc2822(s.trim()).ok())
+            .map(|t| t.timestamp());
+        let checksums = response
+            .descendants()
+            .filter(|n| n.has_tag_name((OC_NS, "checksum")))
+            .filter_map(|n| n.text())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        items.push(DavItem {
+            url,
+            is_dir,
+            size,
+            mtime,
+            checksums,
+        });
+    }
+    Ok(items)
+}
+
+fn find_text<'a>(node: roxmltree::Node<'a, '_>, ns: &str, name: &str) -> Option<&'a str> {
+    node.descendants()
+        .find(|n| n.has_tag_name((ns, name)))
+        .and_then(|n| n.text())
+}
+
+/// Known checksums out of `SHA1:… MD5:… ADLER32:…`, the best one first:
+/// the first of `wanted` the server has, else the first of
+/// [`CHECKSUM_PREFERENCE`]. Empty if none is usable.
+fn server_checksums(checksums: &str, wanted: &[HashAlgorithm]) -> Vec<(String, String)> {
+    let mut parsed: Vec<(HashAlgorithm, String)> = checksums
+        .split_whitespace()
+        .filter_map(|c| c.split_once(':'))
+        .filter(|(_, hex)| !hex.is_empty())
+        .filter_map(|(a, hex)| Some((HashAlgorithm::from_name(a)?, hex.to_lowercase())))
+        .collect();
+
+    let best = wanted
+        .iter()
+        .chain(CHECKSUM_PREFERENCE.iter())
+        .find_map(|algo| parsed.iter().position(|(a, _)| a == algo));
+    let Some(best) = best else {
+        return Vec::new();
+    };
+    let first = parsed.remove(best);
+
+    std::iter::once(first)
+        .chain(parsed)
+        .map(|(a, hex)| (a.name().to_string(), hex))
+        .collect()
+}
+
+fn download_hash(
+    client: &Client,
+    url: &Url,
+    creds: &Credentials,
+    algorithms: &[HashAlgorithm],
+) -> Result<Vec<(String, String)>> {
+    let mut resp = client
+        .get(url.clone())
+        .basic_auth(&creds.user, Some(&creds.password))
+        .send()
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    if !resp.status().is_success() {
+        bail!("HTTP {} for {}", resp.status(), url);
+    }
+
+    let mut hasher = MultiHasher::new(algorithms);
+    let mut buffer = vec![0u8; 2 * 1024 * 1024];
+
+    loop {
+        let n = resp.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Percent-decoded path of a directory URL without the trailing slash, so
+/// differently encoded hrefs of one directory compare equal.
+fn dir_key(url: &Url) -> String {
+    let path = url.path().trim_end_matches('/');
+    match urlencoding::decode(path) {
+        Ok(s) => s.into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// Whether `href` is `dir` itself, also if one of them encodes spaces as `+`.
+fn is_same_dir(href: &Url, dir: &Url) -> bool {
+    let (a, b) = (dir_key(href), dir_key(dir));
+    a == b || a.replace('+', " ") == b.replace('+', " ")
+}
+
+/// Human readable URL with a percent-decoded path.
+fn display_url(url: &Url) -> String {
+    match urlencoding::decode(url.as_str()) {
+        Ok(s) => s.into_owned(),
+        Err(_) => url.to_string(),
+    }
+}
+
+// ─────────────────────────────────────────────
+// Nextcloud trashbin
+// ─────────────────────────────────────────────
+
+/// An item in the Nextcloud trashbin.
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    pub url: Url,
+    /// Path relative to the user's files root before deletion.
+    pub original_location: String,
+    pub size: u64,
+    /// Unix timestamp of the deletion.
+    pub deletion_time: i64,
+}
+
+/// Trashbin URL for a files URL, `…/dav/files/<user>` becomes `…/dav/trashbin/<user>/trash/`.
+pub fn trashbin_url(files_base: &str) -> Result<Url> {
+    let mut url = Url::parse(files_base).with_context(|| format!("Invalid URL {}", files_base))?;
+    let path = url.path().to_string();
+    let (prefix, rest) = path
+        .split_once("/dav/files/")
+        .with_context(|| format!("Cannot derive trashbin URL from {}", files_base))?;
+    let user = rest.split('/').next().unwrap_or_default();
+    url.set_path(&format!("{}/dav/trashbin/{}/trash/", prefix, user));
+    Ok(url)
+}
+
+/// Decoded path of `url` relative to `files_base`, i.e. the trashbin original location.
+pub fn relative_location(files_base: &str, url: &str) -> Option<String> {
+    let rest = url.strip_prefix(files_base.trim_end_matches('/'))?;
+    let rest = rest.strip_prefix('/')?;
+    urlencoding::decode(rest).ok().map(|s| s.into_owned())
+}
+
+/// List the top level of the trashbin.
+pub fn list_trashbin(client: &Client, trash_url: &Url, creds: &Credentials) -> Result<Vec<TrashItem>> {
+    let text = propfind_raw(client, trash_url, creds, TRASHBIN_PROPFIND_BODY)?;
+    let doc = roxmltree::Document::parse(&text).context("Invalid PROPFIND response")?;
+
+    let mut items = Vec::new();
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name((DAV_NS, "response")))
+    {
+        let Some(href) = find_text(response, DAV_NS, "href") else {
+            continue;
+        };
+        let url = trash_url
+            .join(href)
+            .with_context(|| format!("Invalid href {}", href))?;
+        if url.path() == trash_url.path() {
+            continue;
+        }
+        let Some(original_location) = find_text(response, NC_NS, "trashbin-original-location") else {
+            continue;
+        };
+
+        let size = find_text(response, DAV_NS, "getcontentlength")
+            .or_else(|| find_text(response, OC_NS, "size"))
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+        let deletion_time = find_text(response, NC_NS, "trashbin-deletion-time")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+
+        items.push(TrashItem {
+            url,
+            original_location: original_location.to_string(),
+            size,
+            deletion_time,
+        });
+    }
+    Ok(items)
+}
+
+/// Trashbin items that belong to the given original locations.
+///
+/// If a location was deleted more than once only the newest item is returned,
+/// older ones were not produced by this run.
+pub fn matching_trash_items<'a>(
+    items: &'a [TrashItem],
+    locations: &HashSet<String>,
+) -> Vec<&'a TrashItem> {
+    let mut newest: HashMap<&str, &TrashItem> = HashMap::new();
+    for item in items {
+        if !locations.contains(&item.original_location) {
+            continue;
+        }
+        let current = newest.entry(&item.original_location).or_insert(item);
+        if item.deletion_time > current.deletion_time {
+            *current = item;
+        }
+    }
+    let mut matched: Vec<&TrashItem> = newest.into_values().collect();
+    matched.sort_by(|a, b| a.original_location.cmp(&b.original_location));
+    matched
+}
+
+/// Permanently delete a single trashbin item.
+pub fn purge_trash_item(client: &Client, item: &TrashItem, creds: &Credentials) -> Result<()> {
+    let resp = client
+        .delete(item.url.clone())
+        .basic_auth(&creds.user, Some(&creds.password))
+        .send()?;
+    if !resp.status().is_success() {
+        bail!("HTTP {}", resp.status());
+    }
+    Ok(())
+}